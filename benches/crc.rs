@@ -0,0 +1,70 @@
+use chanker::{crc16, crc32};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/**
+ * Bit at a time CRC-16/CCITT-FALSE the table-driven `crc16` replaced
+ */
+fn crc16_bitwise(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+/**
+ * Bit at a time CRC-32/ISO-HDLC the table-driven `crc32` replaced
+ */
+fn crc32_bitwise(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xEDB88320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    !crc
+}
+
+fn bench_crc(c: &mut Criterion) {
+    let data: Vec<u8> = (0..64 * 1024).map(|i| i as u8).collect();
+    assert_eq!(crc16(&data), crc16_bitwise(&data));
+    assert_eq!(crc32(&data), crc32_bitwise(&data));
+
+    let mut group = c.benchmark_group("crc");
+    for size in [250, 4096, data.len()] {
+        let bytes = &data[..size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("crc16_table", size), bytes, |b, bytes| {
+            b.iter(|| crc16(black_box(bytes)))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("crc16_bitwise", size),
+            bytes,
+            |b, bytes| b.iter(|| crc16_bitwise(black_box(bytes))),
+        );
+        group.bench_with_input(BenchmarkId::new("crc32_table", size), bytes, |b, bytes| {
+            b.iter(|| crc32(black_box(bytes)))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("crc32_bitwise", size),
+            bytes,
+            |b, bytes| b.iter(|| crc32_bitwise(black_box(bytes))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_crc);
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+use std::io;
+use std::vec;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::Chunk;
+
+impl<'a, B: AsRef<[u8]>> Chunk<'a, B> {
+    /**
+     * Async counterpart of `write_all_to`, awaits each framed chunk
+     * and yields to the runtime between chunks, w is flushed at the end.
+     * Returns total bytes written.
+     * Framing errors are reported as `io::ErrorKind::InvalidInput`
+     */
+    pub async fn write_all_async<W: AsyncWrite + Unpin>(&self, w: &mut W) -> io::Result<usize> {
+        let mut frame = vec![0; self.max_chunk_size];
+        let mut written = 0;
+        for counter in 0..self.total_chunks() {
+            let len = self
+                .encode_into(counter, &mut frame)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            w.write_all(&frame[..len]).await?;
+            written += len;
+            tokio::task::yield_now().await;
+        }
+        w.flush().await?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::read_message;
+
+    #[tokio::test]
+    async fn test_write_all_async() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data);
+        let mut out = Vec::new();
+        let written = chunk.write_all_async(&mut out).await.unwrap();
+        assert_eq!(written, out.len());
+
+        let mut sync = Vec::new();
+        chunk.write_all_to(&mut sync, false).unwrap();
+        assert_eq!(out, sync);
+        assert_eq!(
+            read_message(&mut &out[..], 250, 1000).unwrap(),
+            (0x10, data)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_all_async_duplex() {
+        let data = [7; 1000];
+        let chunk = Chunk::new(250, 0x10, &data[..]);
+        // a small duplex buffer forces back-pressure on every chunk
+        let (mut tx, mut rx) = tokio::io::duplex(64);
+        let reader = async {
+            let mut out = Vec::new();
+            tokio::io::AsyncReadExt::read_to_end(&mut rx, &mut out)
+                .await
+                .unwrap();
+            out
+        };
+        let writer = async {
+            let written = chunk.write_all_async(&mut tx).await.unwrap();
+            drop(tx);
+            written
+        };
+        let (out, written) = tokio::join!(reader, writer);
+        assert_eq!(out.len(), written);
+        assert_eq!(read_message(&mut &out[..], 250, 1000).unwrap().1, data);
+    }
+}
@@ -0,0 +1,384 @@
+use core::marker::PhantomData;
+
+use crate::{Checksum, Chunk, ChunkError, CounterWidth, Endianness, LengthWidth, MetaWidth};
+
+/**
+ * Fluent configuration of `Chunk` with validation on `build()`,
+ * `Chunk::new` stays the simple unchecked path
+ */
+#[derive(Default)]
+pub struct ChunkBuilder<'a, B = &'a [u8]> {
+    max_chunk_size: usize,
+    topic: u8,
+    data: B,
+    length_width: LengthWidth,
+    counter_width: CounterWidth,
+    crc: bool,
+    checksum: Option<&'a dyn Checksum>,
+    endianness: Endianness,
+    version: Option<u8>,
+    last_flag: bool,
+    session_id: Option<u16>,
+    message_crc: bool,
+    chunk_length: bool,
+    chunk_count: bool,
+    repeat_header: bool,
+    terminator: bool,
+    alignment: Option<usize>,
+    header_flags: bool,
+    varint: bool,
+    priority: u8,
+    marker: PhantomData<&'a [u8]>,
+}
+
+impl<'a> ChunkBuilder<'a> {
+    pub fn new() -> Self {
+        ChunkBuilder {
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a, B: AsRef<[u8]>> ChunkBuilder<'a, B> {
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.max_chunk_size = max_chunk_size;
+        self
+    }
+
+    pub fn topic(mut self, topic: impl Into<u8>) -> Self {
+        self.topic = topic.into();
+        self
+    }
+
+    /**
+     * Borrowed slice or any owned `AsRef<[u8]>` storage
+     */
+    pub fn data<D: AsRef<[u8]>>(self, data: D) -> ChunkBuilder<'a, D> {
+        ChunkBuilder {
+            max_chunk_size: self.max_chunk_size,
+            topic: self.topic,
+            data,
+            length_width: self.length_width,
+            counter_width: self.counter_width,
+            crc: self.crc,
+            checksum: self.checksum,
+            endianness: self.endianness,
+            version: self.version,
+            last_flag: self.last_flag,
+            session_id: self.session_id,
+            message_crc: self.message_crc,
+            chunk_length: self.chunk_length,
+            chunk_count: self.chunk_count,
+            repeat_header: self.repeat_header,
+            terminator: self.terminator,
+            alignment: self.alignment,
+            header_flags: self.header_flags,
+            varint: self.varint,
+            priority: self.priority,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn meta_width(self, meta_width: MetaWidth) -> Self {
+        self.length_width(meta_width).counter_width(meta_width)
+    }
+
+    /**
+     * See `Chunk::with_length_width`
+     */
+    pub fn length_width(mut self, length_width: LengthWidth) -> Self {
+        self.length_width = length_width;
+        self
+    }
+
+    /**
+     * See `Chunk::with_counter_width`
+     */
+    pub fn counter_width(mut self, counter_width: CounterWidth) -> Self {
+        self.counter_width = counter_width;
+        self
+    }
+
+    pub fn with_crc(mut self) -> Self {
+        self.crc = true;
+        self
+    }
+
+    /**
+     * See `Chunk::with_checksum`, replaces `with_crc`
+     */
+    pub fn with_checksum(mut self, checksum: &'a dyn Checksum) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    pub fn big_endian(mut self) -> Self {
+        self.endianness = Endianness::Big;
+        self
+    }
+
+    /**
+     * See `Chunk::with_version`
+     */
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /**
+     * See `Chunk::with_last_flag`
+     */
+    pub fn with_last_flag(mut self) -> Self {
+        self.last_flag = true;
+        self
+    }
+
+    /**
+     * See `Chunk::with_session_id`
+     */
+    pub fn with_session_id(mut self, session_id: u16) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    /**
+     * See `Chunk::with_priority`
+     */
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /**
+     * See `Chunk::with_message_crc`, the CRC is computed once in `build()`
+     */
+    pub fn with_message_crc(mut self) -> Self {
+        self.message_crc = true;
+        self
+    }
+
+    /**
+     * See `Chunk::with_chunk_length`
+     */
+    pub fn with_chunk_length(mut self) -> Self {
+        self.chunk_length = true;
+        self
+    }
+
+    /**
+     * See `Chunk::with_chunk_count`
+     */
+    pub fn with_chunk_count(mut self) -> Self {
+        self.chunk_count = true;
+        self
+    }
+
+    /**
+     * See `Chunk::with_repeat_header`
+     */
+    pub fn with_repeat_header(mut self) -> Self {
+        self.repeat_header = true;
+        self
+    }
+
+    /**
+     * See `Chunk::with_terminator`
+     */
+    pub fn with_terminator(mut self) -> Self {
+        self.terminator = true;
+        self
+    }
+
+    /**
+     * See `Chunk::with_alignment`
+     */
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /**
+     * See `Chunk::with_header_flags`
+     */
+    pub fn with_header_flags(mut self) -> Self {
+        self.header_flags = true;
+        self
+    }
+
+    /**
+     * Smallest framing for reliable links, e.g. an in-memory ring buffer:
+     * topic byte, varint data length and varint counter (see
+     * `Chunk::with_varint`), options enabled before are dropped.
+     * A message of up to 127 bytes in a single chunk costs 3 bytes of framing
+     */
+    pub fn minimal(self) -> Self {
+        ChunkBuilder {
+            max_chunk_size: self.max_chunk_size,
+            topic: self.topic,
+            data: self.data,
+            length_width: LengthWidth::default(),
+            counter_width: CounterWidth::default(),
+            crc: false,
+            checksum: None,
+            endianness: Endianness::Little,
+            version: None,
+            last_flag: false,
+            session_id: None,
+            message_crc: false,
+            chunk_length: false,
+            chunk_count: false,
+            repeat_header: false,
+            terminator: false,
+            alignment: None,
+            header_flags: false,
+            varint: true,
+            priority: self.priority,
+            marker: PhantomData,
+        }
+    }
+
+    /**
+     * Fails with `InvalidAlignment` for an alignment `Chunk::with_alignment`
+     * doesn't take, with `ChunkSizeTooSmall` when max_chunk_size can't hold
+     * header, meta, CRC and at least one payload byte,
+     * with `DataTooLarge` when data doesn't fit the length width
+     * or needs more chunks than the counter width counts
+     */
+    pub fn build(self) -> Result<Chunk<'a, B>, ChunkError> {
+        let mut chunk = Chunk::from_data(self.max_chunk_size, self.topic, self.data)
+            .with_length_width(self.length_width)
+            .with_counter_width(self.counter_width)
+            .with_endianness(self.endianness)
+            .with_priority(self.priority);
+        if self.crc {
+            chunk = chunk.with_crc();
+        }
+        if let Some(checksum) = self.checksum {
+            chunk = chunk.with_checksum(checksum);
+        }
+        if let Some(version) = self.version {
+            chunk = chunk.with_version(version);
+        }
+        if self.last_flag {
+            chunk = chunk.with_last_flag();
+        }
+        if let Some(session_id) = self.session_id {
+            chunk = chunk.with_session_id(session_id);
+        }
+        if self.message_crc {
+            chunk = chunk.with_message_crc();
+        }
+        if self.chunk_length {
+            chunk = chunk.with_chunk_length();
+        }
+        if self.chunk_count {
+            chunk = chunk.with_chunk_count();
+        }
+        if self.repeat_header {
+            chunk = chunk.with_repeat_header();
+        }
+        if self.terminator {
+            chunk = chunk.with_terminator();
+        }
+        if let Some(alignment) = self.alignment {
+            chunk = chunk.with_alignment(alignment);
+        }
+        if self.header_flags {
+            chunk = chunk.with_header_flags();
+        }
+        if self.varint {
+            chunk = chunk.with_varint();
+        }
+        chunk.validate()?;
+        Ok(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let data = [0; 1000];
+        let chunk = Chunk::builder()
+            .max_chunk_size(250)
+            .topic(0x10)
+            .data(&data)
+            .meta_width(MetaWidth::U16)
+            .with_crc()
+            .build()
+            .unwrap();
+        assert_eq!(chunk.header(), [0x10, 0xE8, 0x03]);
+        assert_eq!(chunk.total_chunks(), 5);
+        assert_eq!(chunk.chunk(Some(0)).unwrap().0.len(), 250 - 3 - 2 - 2);
+    }
+
+    #[test]
+    fn test_build_big_endian() {
+        let data = [0; 1000];
+        let chunk = Chunk::builder()
+            .max_chunk_size(250)
+            .topic(0x10)
+            .data(&data)
+            .meta_width(MetaWidth::U32)
+            .big_endian()
+            .build()
+            .unwrap();
+        assert_eq!(chunk.header(), [0x10, 0, 0, 0x03, 0xE8]);
+    }
+
+    #[test]
+    fn test_build_minimal() {
+        let data = [7; 100];
+        let chunk = Chunk::builder()
+            .max_chunk_size(250)
+            .topic(0x10)
+            .data(&data)
+            .with_crc()
+            .with_session_id(3)
+            .minimal()
+            .build()
+            .unwrap();
+        assert_eq!(chunk.header(), [0x10, 100]);
+        assert_eq!(chunk.min_chunk_size(), 4);
+        assert_eq!(chunk.wire_size(), 103);
+        let mut out = [0; 250];
+        assert_eq!(chunk.encode_into(0, &mut out).unwrap(), 103);
+        assert_eq!(out[..3], [0, 0x10, 100]);
+
+        let data = [7; 300];
+        let chunk = Chunk::builder()
+            .max_chunk_size(250)
+            .topic(0x10)
+            .data(&data)
+            .minimal()
+            .build()
+            .unwrap();
+        assert_eq!(chunk.header(), [0x10, 0xAC, 0x02]);
+        assert_eq!(
+            Chunk::parse_varint_header(&chunk.header()).unwrap(),
+            (0x10, 300)
+        );
+        assert_eq!(chunk.total_chunks(), 2);
+        assert_eq!(chunk.first_chunk_capacity(), 250 - 1 - 3);
+    }
+
+    #[test]
+    fn test_build_too_small() {
+        let data = [0; 1000];
+        let builder = Chunk::builder()
+            .topic(0x10)
+            .data(&data)
+            .meta_width(MetaWidth::U16);
+        assert!(matches!(
+            builder.max_chunk_size(5).build(),
+            Err(ChunkError::ChunkSizeTooSmall { min: 6 })
+        ));
+        let builder = Chunk::builder()
+            .topic(0x10)
+            .data(&data)
+            .meta_width(MetaWidth::U16);
+        assert!(builder.max_chunk_size(6).build().is_ok());
+    }
+}
@@ -0,0 +1,125 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::{crc, Chunk, ChunkError};
+
+/**
+ * Chunks a refcounted `Bytes` buffer: payloads are `Bytes::slice`s of it,
+ * zero-copy and free of the lifetime of the chunker, e.g. to queue them onto
+ * an async channel. Options are the ones of the wrapped `Chunk`, build it
+ * with `Chunk::from_data(max_chunk_size, topic, bytes)`.
+ */
+pub struct BytesChunker<'a> {
+    chunk: Chunk<'a, Bytes>,
+}
+
+impl<'a> BytesChunker<'a> {
+    pub fn new(chunk: Chunk<'a, Bytes>) -> Self {
+        BytesChunker { chunk }
+    }
+
+    pub fn chunk(&self) -> &Chunk<'a, Bytes> {
+        &self.chunk
+    }
+
+    /**
+     * Payload of the chunk without copying, None past the end of data
+     */
+    pub fn payload(&self, counter: usize) -> Option<Bytes> {
+        let range = self.chunk.try_range(counter).ok()??;
+        Some(self.chunk.data.slice(range))
+    }
+
+    /**
+     * Everything of the `Chunk::encode_into` frame preceding the payload:
+     * counter, chunk length, session id and on the first chunk the header.
+     * Meant for vectored writes of prefix and `payload()`, which only form
+     * the whole frame when neither checksum nor alignment is set,
+     * use `frame()` otherwise
+     */
+    pub fn prefix(&self, counter: usize) -> Result<BytesMut, ChunkError> {
+        let range = self
+            .chunk
+            .try_range(counter)?
+            .ok_or(ChunkError::CounterOutOfRange)?;
+        let mut prefix = BytesMut::zeroed(self.chunk.prefix_len(counter, true));
+        self.chunk
+            .encode_prefix(counter, true, range.len(), &mut prefix)?;
+        Ok(prefix)
+    }
+
+    /**
+     * Whole wire frame of `Chunk::encode_into`, the payload is copied after
+     * `prefix()` and followed by the checksum
+     */
+    pub fn frame(&self, counter: usize) -> Result<Bytes, ChunkError> {
+        let mut frame = self.prefix(counter)?;
+        let payload = self.payload(counter).ok_or(ChunkError::CounterOutOfRange)?;
+        frame.extend_from_slice(&payload);
+        let padding = self.chunk.padding(frame.len() + self.chunk.crc_size);
+        frame.resize(frame.len() + padding, 0);
+        if let Some(checksum) = self.chunk.checksum {
+            let pos = frame.len();
+            frame.resize(pos + self.chunk.crc_size, 0);
+            crc::put_checksum(checksum, &mut frame, pos);
+        }
+        Ok(frame.freeze())
+    }
+}
+
+impl Iterator for BytesChunker<'_> {
+    type Item = Bytes;
+
+    /**
+     * Next payload from the iteration position of the wrapped `Chunk`
+     */
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunk.counter >= self.chunk.back_counter() {
+            return None;
+        }
+        let payload = self.payload(self.chunk.counter)?;
+        self.chunk.inc_counter();
+        Some(payload)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.chunk.back_counter().saturating_sub(self.chunk.counter);
+        (remaining, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_bytes_payloads() {
+        let data = Bytes::from((0..1000).map(|i| i as u8).collect::<Vec<u8>>());
+        let chunker = BytesChunker::new(Chunk::from_data(250, 0x10, data.clone()));
+        let reference = Chunk::new(250, 0x10, &data);
+        let payloads: Vec<Bytes> = chunker.collect();
+        assert_eq!(payloads.len(), reference.total_chunks());
+        for (payload, (expected, _)) in payloads.iter().zip(reference) {
+            assert_eq!(payload, expected);
+            assert!(data.as_ptr_range().contains(&payload.as_ptr()));
+        }
+    }
+
+    #[test]
+    fn test_bytes_frames() {
+        let data = Bytes::from((0..1000).map(|i| i as u8).collect::<Vec<u8>>());
+        let chunker = BytesChunker::new(Chunk::from_data(250, 0x10, data.clone()).with_crc());
+        let reference = Chunk::new(250, 0x10, &data).with_crc();
+        let mut out = [0; 250];
+        for counter in 0..reference.total_chunks() {
+            let len = reference.encode_into(counter, &mut out).unwrap();
+            assert_eq!(chunker.frame(counter).unwrap(), out[..len]);
+            let prefix = chunker.prefix(counter).unwrap();
+            assert_eq!(prefix, out[..prefix.len()]);
+        }
+        assert!(matches!(
+            chunker.frame(reference.total_chunks()),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{Chunk, ChunkError, Endianness, Header, MetaWidth};
+
+/**
+ * `Chunk` with max_chunk_size fixed at compile time, frames are encoded
+ * into `[u8; MAX]` arrays on the stack, no heap involved.
+ * MAX below the smallest possible framing (U16 meta, header, one payload byte)
+ * fails to compile, the configured options are checked by `validate()`.
+ */
+#[derive(Default)]
+pub struct ChunkN<'a, const MAX: usize> {
+    counter: usize,
+    topic: u8,
+    data: &'a [u8],
+    meta_width: MetaWidth,
+    crc: bool,
+    endianness: Endianness,
+}
+
+impl<'a, const MAX: usize> ChunkN<'a, MAX> {
+    const MIN_MAX: () = assert!(
+        MAX > 1 + 2 * MetaWidth::U16.size(),
+        "MAX can't hold the framing"
+    );
+
+    pub fn new(topic: impl Into<u8>, data: &'a [u8]) -> Self {
+        let () = Self::MIN_MAX;
+        ChunkN {
+            topic: topic.into(),
+            data,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_crc(mut self) -> Self {
+        self.crc = true;
+        self
+    }
+
+    pub fn with_meta_width(mut self, meta_width: MetaWidth) -> Self {
+        self.meta_width = meta_width;
+        self
+    }
+
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /**
+     * Fails with `ChunkSizeTooSmall` when MAX can't hold the configured framing
+     */
+    pub fn validate(&self) -> Result<(), ChunkError> {
+        self.chunk().validate()
+    }
+
+    pub fn header(&self) -> Header {
+        self.chunk().header()
+    }
+
+    pub fn total_chunks(&self) -> usize {
+        self.chunk().total_chunks()
+    }
+
+    pub fn counter(&self) -> usize {
+        self.counter
+    }
+
+    /**
+     * Wire frame of the chunk and its length, see `Chunk::encode_into`
+     */
+    pub fn encode(&self, counter: usize) -> Result<([u8; MAX], usize), ChunkError> {
+        let mut out = [0; MAX];
+        let len = self.encode_into(counter, &mut out)?;
+        Ok((out, len))
+    }
+
+    pub fn encode_into(&self, counter: usize, out: &mut [u8; MAX]) -> Result<usize, ChunkError> {
+        self.chunk().encode_into(counter, out)
+    }
+
+    /**
+     * Same layout rules as `Chunk`, built on demand as it is just a few words
+     */
+    fn chunk(&self) -> Chunk<'a> {
+        let chunk = Chunk::new(MAX, self.topic, self.data)
+            .with_meta_width(self.meta_width)
+            .with_endianness(self.endianness);
+        if self.crc {
+            chunk.with_crc()
+        } else {
+            chunk
+        }
+    }
+}
+
+impl<'a, const MAX: usize> Iterator for ChunkN<'a, MAX> {
+    type Item = (&'a [u8], usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = self.chunk();
+        chunk.seek(self.counter);
+        let item = chunk.next()?;
+        self.counter += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.total_chunks().saturating_sub(self.counter);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, const MAX: usize> ExactSizeIterator for ChunkN<'a, MAX> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_n() {
+        let data = [0x55; 1000];
+        let chunk_n = ChunkN::<250>::new(0x10, &data).with_crc();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        assert_eq!(chunk_n.total_chunks(), chunk.total_chunks());
+        assert_eq!(chunk_n.header(), chunk.header());
+        for counter in 0..chunk.total_chunks() {
+            let (frame, len) = chunk_n.encode(counter).unwrap();
+            let mut out = [0; 250];
+            assert_eq!(chunk.encode_into(counter, &mut out).unwrap(), len);
+            assert_eq!(frame, out);
+        }
+        assert!(chunk_n.zip(chunk).all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn test_chunk_n_validate() {
+        let data = [0; 10];
+        assert!(matches!(
+            ChunkN::<10>::new(0x10, &data).validate(),
+            Err(ChunkError::ChunkSizeTooSmall { .. })
+        ));
+        let chunk = ChunkN::<10>::new(0x10, &data).with_meta_width(MetaWidth::U16);
+        assert!(chunk.validate().is_ok());
+        assert_eq!(chunk.count(), 2);
+    }
+}
@@ -0,0 +1,169 @@
+use alloc::vec::Vec;
+
+use miniz_oxide::inflate::TINFLStatus;
+
+use crate::{Chunk, ChunkError};
+
+const LEVEL: u8 = 6;
+
+impl<'a> Chunk<'a, Vec<u8>> {
+    /**
+     * Chunks zlib-deflated data instead of data itself.
+     * The header declares the compressed length with its top bit set as the
+     * compressed flag, a `Dechunker::with_compression` inflates on completion
+     */
+    pub fn new_compressed(max_chunk_size: usize, topic: impl Into<u8>, data: &[u8]) -> Self {
+        let mut chunk = Chunk::from_data(
+            max_chunk_size,
+            topic,
+            miniz_oxide::deflate::compress_to_vec_zlib(data, LEVEL),
+        );
+        chunk.compressed = true;
+        chunk
+    }
+}
+
+/**
+ * Inflated data of at most max bytes, more fail with `MessageTooLarge`
+ * whose got is max + 1, the full length isn't inflated to learn it
+ */
+pub(crate) fn inflate(data: &[u8], max: Option<usize>) -> Result<Vec<u8>, ChunkError> {
+    let limit = max.unwrap_or(usize::MAX);
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, limit).map_err(|err| {
+        match (max, err.status) {
+            (Some(max), TINFLStatus::HasMoreOutput) => ChunkError::MessageTooLarge {
+                max,
+                got: max.saturating_add(1),
+            },
+            _ => ChunkError::InflateFailed,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dechunker, MetaWidth, FLAG_COMPRESSED};
+    use std::vec;
+
+    fn push_all<B: AsRef<[u8]>>(dechunker: &mut Dechunker, chunk: &Chunk<B>) -> Vec<u8> {
+        let mut result = None;
+        for counter in 0..chunk.total_chunks() {
+            let mut frame = [0; 250];
+            let len = chunk.encode_into(counter, &mut frame).unwrap();
+            result = dechunker.push(&frame[..len]).unwrap();
+        }
+        result.unwrap()
+    }
+
+    #[test]
+    fn test_compressed() {
+        let data: Vec<u8> = (0..4000).map(|i| (i % 16) as u8).collect();
+        let chunk = Chunk::new_compressed(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert!(chunk.data().len() < data.len());
+        assert_eq!(chunk.total_chunks(), 1);
+        let header = chunk.header();
+        assert_eq!(header[2] & 0x80, 0x80);
+
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U16)
+            .with_compression();
+        assert_eq!(push_all(&mut dechunker, &chunk), data);
+
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U16)
+            .with_compression();
+        let plain = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert_eq!(push_all(&mut dechunker, &plain), data);
+    }
+
+    #[test]
+    fn test_compressed_too_large() {
+        let chunk = Chunk::from_data(250, 0x10, vec![0; 0x8000]).with_meta_width(MetaWidth::U16);
+        let mut chunk = Chunk {
+            compressed: true,
+            ..chunk
+        };
+        let mut out = [0; 250];
+        assert!(matches!(
+            chunk.encode_into(0, &mut out),
+            Err(ChunkError::DataTooLarge { max: 0x7FFF })
+        ));
+        chunk.data.truncate(0x7FFF);
+        assert!(chunk.encode_into(0, &mut out).is_ok());
+    }
+
+    #[test]
+    fn test_compressed_header_flags() {
+        let data: Vec<u8> = (0..4000).map(|i| (i % 16) as u8).collect();
+        let chunk = Chunk::new_compressed(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_header_flags();
+        let header = chunk.header();
+        assert_eq!(header[1], FLAG_COMPRESSED);
+        assert_eq!(header[3] & 0x80, 0);
+        assert_eq!(chunk.max_data_len(), 0xFFFF);
+
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U16)
+            .with_compression()
+            .with_header_flags();
+        assert_eq!(push_all(&mut dechunker, &chunk), data);
+
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U16)
+            .with_header_flags();
+        let mut frame = [0; 250];
+        let len = chunk.encode_into(0, &mut frame).unwrap();
+        assert!(matches!(
+            dechunker.push(&frame[..len]),
+            Err(ChunkError::FlagsMismatch {
+                expected: 0,
+                got: FLAG_COMPRESSED
+            })
+        ));
+    }
+
+    #[test]
+    fn test_compressed_varint() {
+        let data: Vec<u8> = (0..4000).map(|i| (i % 16) as u8).collect();
+        let chunk = Chunk::new_compressed(250, 0x10, &data).with_varint();
+        let len = chunk.data().len();
+        assert!(len < 0x40);
+        assert_eq!(chunk.header(), [0x10, len as u8 | 0x40]);
+
+        let mut dechunker = Dechunker::new().with_varint().with_compression();
+        assert_eq!(push_all(&mut dechunker, &chunk), data);
+    }
+
+    #[test]
+    fn test_inflate_failed() {
+        assert!(matches!(
+            inflate(&[1, 2, 3], None),
+            Err(ChunkError::InflateFailed)
+        ));
+    }
+
+    #[test]
+    fn test_inflate_max_message_len() {
+        let data = vec![0; 100_000];
+        let chunk = Chunk::new_compressed(250, 0x10, &data);
+        assert_eq!(chunk.total_chunks(), 1);
+        let mut frame = [0; 250];
+        let len = chunk.encode_into(0, &mut frame).unwrap();
+        let mut dechunker = Dechunker::new()
+            .with_compression()
+            .with_max_message_len(1000);
+        assert!(matches!(
+            dechunker.push(&frame[..len]),
+            Err(ChunkError::MessageTooLarge {
+                max: 1000,
+                got: 1001
+            })
+        ));
+        let mut dechunker = Dechunker::new()
+            .with_compression()
+            .with_max_message_len(100_000);
+        assert_eq!(dechunker.push(&frame[..len]).unwrap().unwrap(), data);
+    }
+}
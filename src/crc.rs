@@ -0,0 +1,281 @@
+use core::fmt;
+
+use crate::ChunkError;
+
+pub const CRC_SIZE: usize = core::mem::size_of::<u16>();
+pub const MESSAGE_CRC_SIZE: usize = core::mem::size_of::<u32>();
+const MAX_CHECKSUM_SIZE: usize = core::mem::size_of::<u64>();
+
+/**
+ * Integrity check appended to every frame, see `Chunk::with_checksum`.
+ * Sync, so chunks holding one can be sent to other threads
+ */
+pub trait Checksum: Sync {
+    /**
+     * Bytes reserved at the end of every frame, at most 8, wider is capped
+     */
+    fn width(&self) -> usize;
+
+    /**
+     * Checksum of bytes, its low `width()` bytes go on the wire little-endian
+     */
+    fn compute(&self, bytes: &[u8]) -> u64;
+}
+
+impl fmt::Debug for dyn Checksum + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Checksum({} bytes)", self.width())
+    }
+}
+
+/**
+ * `crc16`, the checksum of `Chunk::with_crc`
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Crc16;
+
+impl Checksum for Crc16 {
+    fn width(&self) -> usize {
+        CRC_SIZE
+    }
+
+    fn compute(&self, bytes: &[u8]) -> u64 {
+        crc16(bytes) as u64
+    }
+}
+
+/**
+ * `crc32`
+ */
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Crc32;
+
+impl Checksum for Crc32 {
+    fn width(&self) -> usize {
+        core::mem::size_of::<u32>()
+    }
+
+    fn compute(&self, bytes: &[u8]) -> u64 {
+        crc32(bytes) as u64
+    }
+}
+
+/**
+ * CRC-8/SMBUS: poly 0x07, init 0, no reflection, no final xor
+ */
+#[cfg(feature = "crc8")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Crc8;
+
+#[cfg(feature = "crc8")]
+impl Checksum for Crc8 {
+    fn width(&self) -> usize {
+        1
+    }
+
+    fn compute(&self, bytes: &[u8]) -> u64 {
+        let mut crc: u8 = 0;
+        for byte in bytes {
+            crc ^= byte;
+            for _ in 0..8 {
+                if crc & 0x80 != 0 {
+                    crc = (crc << 1) ^ 0x07;
+                } else {
+                    crc <<= 1;
+                }
+            }
+        }
+        crc as u64
+    }
+}
+
+pub(crate) fn checksum_width(checksum: &dyn Checksum) -> usize {
+    checksum.width().min(MAX_CHECKSUM_SIZE)
+}
+
+/**
+ * CRC of every byte value for the byte-at-a-time lookup,
+ * built at compile time and kept in flash on embedded targets
+ */
+static CRC16_TABLE: [u16; 256] = crc16_table();
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc16_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < table.len() {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < table.len() {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/**
+ * CRC-16/CCITT-FALSE: poly 0x1021, init 0xFFFF, no reflection, no final xor
+ */
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for byte in bytes {
+        crc = (crc << 8) ^ CRC16_TABLE[((crc >> 8) as u8 ^ byte) as usize];
+    }
+    crc
+}
+
+/**
+ * CRC-32/ISO-HDLC (zlib, Ethernet): reflected poly 0xEDB88320, init and final xor 0xFFFFFFFF
+ */
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_append(0, bytes)
+}
+
+/**
+ * CRC-32 of the bytes the crc was computed over followed by bytes,
+ * crc32 of the empty slice is 0
+ */
+pub(crate) fn crc32_append(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in bytes {
+        crc = (crc >> 8) ^ CRC32_TABLE[(crc as u8 ^ byte) as usize];
+    }
+    !crc
+}
+
+/**
+ * Checks the trailing CRC of a frame and returns the frame without it
+ */
+pub fn verify_crc(frame: &[u8]) -> Result<&[u8], ChunkError> {
+    verify_checksum(frame, &Crc16)
+}
+
+/**
+ * Same as `verify_crc` for any `Checksum`
+ */
+pub fn verify_checksum<'f>(
+    frame: &'f [u8],
+    checksum: &dyn Checksum,
+) -> Result<&'f [u8], ChunkError> {
+    let width = checksum_width(checksum);
+    if frame.len() < width {
+        return Err(ChunkError::InvalidMetaSize {
+            expected: width,
+            got: frame.len(),
+        });
+    }
+    let (body, sum) = frame.split_at(frame.len() - width);
+    if checksum.compute(body).to_le_bytes()[..width] != *sum {
+        return Err(ChunkError::CrcMismatch);
+    }
+    Ok(body)
+}
+
+/**
+ * Appends the checksum of out[..pos] at pos, returns the new end
+ */
+pub(crate) fn put_checksum(checksum: &dyn Checksum, out: &mut [u8], pos: usize) -> usize {
+    let width = checksum_width(checksum);
+    let sum = checksum.compute(&out[..pos]).to_le_bytes();
+    out[pos..pos + width].copy_from_slice(&sum[..width]);
+    pos + width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(b""), 0xFFFF);
+        assert_eq!(CRC16_TABLE[1], 0x1021);
+        assert_eq!(CRC16_TABLE[255], 0x1EF0);
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414FA339
+        );
+        assert_eq!(CRC32_TABLE[1], 0x77073096);
+        assert_eq!(crc32_append(crc32(b"1234"), b"56789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_verify_crc() {
+        let mut frame = [0x10, 0x20, 0x30, 0, 0];
+        let crc = crc16(&frame[..3]).to_le_bytes();
+        frame[3..].copy_from_slice(&crc);
+        assert_eq!(verify_crc(&frame).unwrap(), &[0x10, 0x20, 0x30]);
+
+        frame[0] = 0x11;
+        assert!(matches!(verify_crc(&frame), Err(ChunkError::CrcMismatch)));
+    }
+
+    struct Sum8;
+
+    impl Checksum for Sum8 {
+        fn width(&self) -> usize {
+            1
+        }
+
+        fn compute(&self, bytes: &[u8]) -> u64 {
+            bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) as u64
+        }
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let mut frame = [0x10, 0x20, 0x30, 0];
+        assert_eq!(put_checksum(&Sum8, &mut frame, 3), 4);
+        assert_eq!(frame[3], 0x60);
+        assert_eq!(verify_checksum(&frame, &Sum8).unwrap(), &[0x10, 0x20, 0x30]);
+        frame[0] = 0x11;
+        assert!(matches!(
+            verify_checksum(&frame, &Sum8),
+            Err(ChunkError::CrcMismatch)
+        ));
+
+        let mut frame = [0x10, 0x20, 0x30, 0, 0, 0, 0];
+        put_checksum(&Crc32, &mut frame, 3);
+        assert_eq!(frame[3..], crc32(&frame[..3]).to_le_bytes());
+        assert!(verify_checksum(&frame, &Crc32).is_ok());
+    }
+
+    #[cfg(feature = "crc8")]
+    #[test]
+    fn test_crc8() {
+        assert_eq!(Crc8.compute(b"123456789"), 0xF4);
+    }
+}
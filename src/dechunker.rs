@@ -0,0 +1,1975 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::crc::{checksum_width, crc32_append, Crc16};
+use crate::format::{DecodedFrame, FirstHeader, FrameFormat};
+use crate::{
+    Checksum, ChunkError, CounterWidth, Endianness, Frame, HeaderFlags, LengthWidth, MetaWidth,
+};
+
+/**
+ * Receiver side counterpart of `Chunk`.
+ *
+ * Every frame starts with the meta counter, the first frame (counter 0)
+ * additionally carries `header()` right after it:
+ *
+ * [counter: meta][topic: u8][len: meta][payload] - first chunk
+ * [counter: meta][payload]                        - subsequent chunks
+ *
+ * where meta is `MetaWidth` wide, u64 by default, or the counter
+ * `CounterWidth` and the length `LengthWidth` wide when they differ.
+ *
+ * With `with_crc()` every frame is followed by its CRC-16
+ * (or the checksum of `with_checksum()`),
+ * with `with_version()` the header starts with the version byte,
+ * with `with_last_flag()` the top bit of the counter marks the final chunk,
+ * with `with_session_ids()` the counter is followed by the u16 session id,
+ * with `with_message_crc()` the header ends with CRC-32 of the whole data,
+ * with `with_chunk_length()` the counter is followed by the payload length,
+ * with `with_header_flags()` the topic is followed by the flags byte,
+ * with `with_chunk_count()` the length is followed by the final counter,
+ * with `with_repeat_header()` every frame carries the header,
+ * with `with_terminator()` the message ends with a frame without payload
+ * and the top bit of its counter set,
+ * with `with_alignment()` the session id is followed by the padding length
+ * and the payload by as many zero bytes,
+ * with `with_varint()` counter and lengths are varints instead of meta.
+ */
+#[derive(Default, Debug, Clone)]
+pub struct Dechunker {
+    topic: Option<u8>,
+    len: Option<usize>,
+    received: usize,
+    chunks: BTreeMap<usize, Vec<u8>>,
+    complete: bool,
+    format: FrameFormat,
+    inflate: bool,
+    last: Option<usize>,
+    session_id: Option<u16>,
+    expected_crc: Option<u32>,
+    flags: Option<HeaderFlags>,
+    header_len: Option<usize>,
+    total: Option<usize>,
+    max_chunk_size: Option<usize>,
+    max_message_len: Option<usize>,
+    first_len: Option<usize>,
+    drain_next: usize,
+    drained: usize,
+    drained_crc: u32,
+    drain_buf: Vec<u8>,
+}
+
+impl Dechunker {
+    pub fn new() -> Self {
+        Dechunker {
+            ..Default::default()
+        }
+    }
+
+    /**
+     * Expects every frame to end with a CRC-16, see `Chunk::with_crc`
+     */
+    pub fn with_crc(self) -> Self {
+        self.with_checksum(&Crc16)
+    }
+
+    /**
+     * Expects every frame to end with the checksum, must be the one of
+     * `Chunk::with_checksum`, frames failing it are rejected with `CrcMismatch`
+     */
+    pub fn with_checksum(mut self, checksum: &'static dyn Checksum) -> Self {
+        self.format.checksum = Some(checksum);
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_meta_width` of the sender
+     */
+    pub fn with_meta_width(self, meta_width: MetaWidth) -> Self {
+        self.with_length_width(meta_width)
+            .with_counter_width(meta_width)
+    }
+
+    /**
+     * Must match `Chunk::with_length_width` of the sender
+     */
+    pub fn with_length_width(mut self, length_width: LengthWidth) -> Self {
+        self.format.length_width = length_width;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_counter_width` of the sender
+     */
+    pub fn with_counter_width(mut self, counter_width: CounterWidth) -> Self {
+        self.format.counter_width = counter_width;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_endianness` of the sender
+     */
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.format.endianness = endianness;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_version` of the sender,
+     * headers with another version are rejected with `UnsupportedVersion`
+     */
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.format.version = Some(version);
+        self
+    }
+
+    /**
+     * Treats the top bit of the header length as the compressed flag
+     * and inflates flagged messages on completion, see `Chunk::new_compressed`
+     */
+    #[cfg(feature = "compress")]
+    pub fn with_compression(mut self) -> Self {
+        self.format.compression = true;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_last_flag` of the sender
+     */
+    pub fn with_last_flag(mut self) -> Self {
+        self.format.last_flag = true;
+        self
+    }
+
+    /**
+     * Expects every frame to carry a session id, see `Chunk::with_session_id`.
+     * The first frame pushed binds the dechunker to its session, frames of
+     * other sessions are rejected with `SessionMismatch`, so they can be routed
+     * to another dechunker or dropped
+     */
+    pub fn with_session_ids(mut self) -> Self {
+        self.format.session_ids = true;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_message_crc` of the sender, the reassembled data
+     * is checked against it and rejected with `MessageCrcMismatch`
+     */
+    pub fn with_message_crc(mut self) -> Self {
+        self.format.message_crc = true;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_chunk_length` of the sender, frames with less payload
+     * than declared are rejected with `TruncatedChunk`
+     */
+    pub fn with_chunk_length(mut self) -> Self {
+        self.format.chunk_length = true;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_header_flags` of the sender. The flags of the header
+     * are checked against the options of this dechunker, a mismatch is rejected
+     * with `FlagsMismatch`. Compressed messages are accepted with `with_compression()`
+     */
+    pub fn with_header_flags(mut self) -> Self {
+        self.format.header_flags = true;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_chunk_count` of the sender. Once the first chunk
+     * arrived `missing()` covers every chunk up to the final one, `finish()`
+     * reports a lost final chunk and counters beyond the declared number of
+     * chunks are rejected with `ChunkCountMismatch`
+     */
+    pub fn with_chunk_count(mut self) -> Self {
+        self.format.chunk_count = true;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_repeat_header` of the sender. Every chunk then
+     * carries topic and length, so `missing()` and `decode_frame` know them
+     * from whichever chunk arrives first
+     */
+    pub fn with_repeat_header(mut self) -> Self {
+        self.format.repeat_header = true;
+        self
+    }
+
+    /**
+     * Rejects the header of any other topic with `UnexpectedTopic`, before
+     * anything of the message is stored, e.g. for traffic of a shared link
+     * this receiver doesn't handle
+     */
+    pub fn allowed_topics(&mut self, topics: &[u8]) {
+        self.format.allow_topics(topics);
+    }
+
+    /**
+     * Must match `Chunk::with_terminator` of the sender. The message completes
+     * once the terminator and every chunk before it arrived, a message shorter
+     * than the declared length then fails with `LengthMismatch`. `missing()`
+     * covers every chunk up to the terminator once it arrived
+     */
+    pub fn with_terminator(mut self) -> Self {
+        self.format.terminator = true;
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_alignment` of the sender, the padding is
+     * stripped by its recorded length. Frames that aren't a multiple of
+     * alignment are rejected with `LengthMismatch`
+     */
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.format.alignment = Some(alignment);
+        self
+    }
+
+    /**
+     * Must match `Chunk::with_varint` of the sender, e.g. `ChunkBuilder::minimal`.
+     * The header length is then known once the first chunk arrived,
+     * `Frame::data_offset` assumes the shortest one before
+     */
+    pub fn with_varint(mut self) -> Self {
+        self.format.varint = true;
+        self
+    }
+
+    /**
+     * max_chunk_size of the sender, lets `decode_frame` tell `Frame::data_offset`
+     * of any chunk. Without it the offset is derived from the first chunk
+     * pushed so far, or else from the size of the frame itself,
+     * which is right for every chunk but the final one
+     */
+    pub fn with_max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.max_chunk_size = Some(max_chunk_size);
+        self
+    }
+
+    /**
+     * Rejects headers declaring more than max_message_len bytes with
+     * `MessageTooLarge` before anything is buffered, a guard against corrupt
+     * or hostile headers on untrusted links. Counters arriving before the
+     * header are bounded by it (see `push`), and compressed messages
+     * inflating to more fail with `MessageTooLarge` as well
+     */
+    pub fn with_max_message_len(mut self, max_message_len: usize) -> Self {
+        self.max_message_len = Some(max_message_len);
+        self
+    }
+
+    /**
+     * Streams the data into sink instead of reassembling it: every `push` of
+     * `SinkDechunker` hands the bytes that became contiguous to sink,
+     * only chunks arriving out of order are buffered
+     */
+    pub fn with_sink<F>(self, sink: F) -> SinkDechunker<F>
+    where
+        F: FnMut(&[u8]) -> Result<(), ChunkError>,
+    {
+        SinkDechunker {
+            dechunker: self,
+            sink,
+        }
+    }
+
+    /**
+     * Flags of the header received so far, `with_header_flags()` only
+     */
+    pub fn flags(&self) -> Option<HeaderFlags> {
+        self.flags
+    }
+
+    pub fn session_id(&self) -> Option<u16> {
+        self.session_id
+    }
+
+    pub fn topic(&self) -> Option<u8> {
+        self.topic
+    }
+
+    /**
+     * Topic converted into a user type, see `Chunk::parse_header_as`
+     */
+    pub fn topic_as<T: TryFrom<u8>>(&self) -> Result<Option<T>, ChunkError> {
+        self.topic
+            .map(|topic| T::try_from(topic).map_err(|_| ChunkError::InvalidTopic(topic)))
+            .transpose()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /**
+     * Number of distinct chunks received so far
+     */
+    pub fn received_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /**
+     * Fraction of the data length declared in the header received so far,
+     * 0.0 until the header arrives, 1.0 for a complete (or empty) message
+     */
+    pub fn progress(&self) -> f32 {
+        match self.len {
+            Some(0) => 1.0,
+            Some(len) => self.received.min(len) as f32 / len as f32,
+            None => 0.0,
+        }
+    }
+
+    /**
+     * Bytes of the declared data length not received yet,
+     * None until the header arrives
+     */
+    pub fn bytes_remaining(&self) -> Option<usize> {
+        self.len.map(|len| len.saturating_sub(self.received))
+    }
+
+    /**
+     * Counters not received yet, to ask the sender for them.
+     * Unless `with_chunk_count()` puts the number of chunks on the wire, these are
+     * the gaps up to the highest counter received, or up to the final chunk
+     * with `with_last_flag()`. Once the header arrived `push` rejects counters
+     * past its length, so they are as many as the data has bytes at most,
+     * before that only `with_max_message_len()` bounds them
+     */
+    pub fn missing(&self) -> Vec<usize> {
+        if self.complete {
+            return Vec::new();
+        }
+        let end = match (self.last, self.chunks.keys().next_back()) {
+            (Some(last), _) => last,
+            (None, Some(&highest)) => highest,
+            (None, None) => return Vec::new(),
+        };
+        (0..=end)
+            .filter(|counter| !self.chunks.contains_key(counter))
+            .collect()
+    }
+
+    /**
+     * Call when the transport has nothing more to deliver.
+     * Fails with `MissingFinalChunk` when the final chunk never arrived
+     * (`with_last_flag()`, `with_chunk_count()` or `with_terminator()` only)
+     * and with `LengthMismatch`
+     * for any other gap
+     */
+    pub fn finish(&self) -> Result<(), ChunkError> {
+        if self.complete {
+            return Ok(());
+        }
+        let final_received = self
+            .last
+            .is_some_and(|last| self.chunks.contains_key(&last));
+        let final_known =
+            self.format.last_flag || self.format.chunk_count || self.format.terminator;
+        if final_known && !final_received {
+            return Err(ChunkError::MissingFinalChunk);
+        }
+        Err(ChunkError::LengthMismatch {
+            expected: self.len.unwrap_or(0),
+            got: self.received,
+        })
+    }
+
+    /**
+     * Hands out the payloads received in order from the drain cursor on,
+     * coalesced into one slice, with their offset in the data, and moves the
+     * cursor past them. Never skips a gap: None until the chunk at the cursor
+     * arrives, and always None for compressed messages.
+     * Drained payloads are dropped, `push()` then completes with the rest only
+     */
+    pub fn drain_contiguous(&mut self) -> Option<(usize, &[u8])> {
+        if self.inflate {
+            return None;
+        }
+        self.drain_buf.clear();
+        while let Some(payload) = self.chunks.get_mut(&self.drain_next) {
+            // the key stays to detect late duplicates
+            self.drain_buf.append(payload);
+            self.drain_next += 1;
+        }
+        if self.drain_buf.is_empty() {
+            return None;
+        }
+        let offset = self.drained;
+        self.drained += self.drain_buf.len();
+        self.drained_crc = crc32_append(self.drained_crc, &self.drain_buf);
+        Some((offset, &self.drain_buf))
+    }
+
+    /**
+     * Decodes a single wire frame without storing it.
+     * Subsequent frames don't carry the topic, it is the one of the first frame
+     * pushed so far, 0 when none was. A counter whose data offset overflows
+     * fails with `CounterOutOfRange`
+     */
+    pub fn decode_frame<'f>(&self, frame: &'f [u8]) -> Result<Frame<'f>, ChunkError> {
+        let decoded = self.format.decode(frame)?;
+        if decoded.aborted {
+            return Err(ChunkError::Aborted);
+        }
+        let (topic, single) = match &decoded.header {
+            Some(header) => (header.topic, decoded.payload.len() == header.len),
+            None => (self.topic.unwrap_or(0), false),
+        };
+        let total_chunks = match &decoded.header {
+            Some(header) => header.chunk_count,
+            None => self.total,
+        };
+        let is_last = match total_chunks {
+            _ if self.format.last_flag => decoded.is_last,
+            _ if self.format.terminator => self.is_terminator(&decoded),
+            Some(total) => decoded.counter + 1 == total,
+            None => single,
+        };
+        Ok(Frame {
+            topic,
+            counter: decoded.counter,
+            data_offset: self.data_offset(&decoded)?,
+            payload: decoded.payload,
+            session_id: decoded.session_id,
+            is_first: decoded.counter == 0,
+            is_last,
+            total_chunks,
+        })
+    }
+
+    /**
+     * Accepts a single wire frame, chunks may arrive in any order
+     * and more than once, repeated chunks are ignored.
+     * Returns reassembled data once the length declared in the header is reached,
+     * less the part handed out by `drain_contiguous()`. A counter the declared
+     * length can't reach, a byte per chunk after the first, fails with
+     * `CounterOutOfRange`.
+     * An abort frame of `Chunk::encode_abort` drops everything of the message
+     * and fails with `Aborted`, the next message starts from scratch.
+     * With `with_chunk_length()` and a checksum, a frame failing its checksum
+     * is searched for the first frame of a new message, see `find_header`, for
+     * streams that lost frame boundaries: when one is found whose length and
+     * checksum check out, the message received so far is dropped and that
+     * frame is pushed instead. Otherwise it fails with `CrcMismatch` and
+     * leaves the message as it is.
+     */
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, ChunkError> {
+        let decoded = match self.format.decode(frame) {
+            Err(ChunkError::CrcMismatch) => return self.resync(frame),
+            decoded => decoded?,
+        };
+        let counter = decoded.counter;
+        match (self.session_id, decoded.session_id) {
+            (Some(expected), Some(got)) if expected != got => {
+                return Err(ChunkError::SessionMismatch { expected, got });
+            }
+            (None, Some(_)) => self.session_id = decoded.session_id,
+            _ => {}
+        }
+        if decoded.aborted {
+            self.start_over();
+            return Err(ChunkError::Aborted);
+        }
+        // retransmitted chunks that did arrive before are ignored
+        if self.complete || self.chunks.contains_key(&counter) {
+            return Ok(None);
+        }
+        if let Some(header) = &decoded.header {
+            self.check_header(header)?;
+        }
+        // a late first chunk declaring fewer chunks than arrived is caught here too
+        let highest = self
+            .chunks
+            .keys()
+            .next_back()
+            .map_or(counter, |&k| k.max(counter));
+        let len = decoded
+            .header
+            .as_ref()
+            .map_or(self.len, |header| Some(header.len));
+        let first_len = match counter {
+            0 => Some(decoded.payload.len()),
+            _ => self.first_len,
+        };
+        self.check_counter(highest, len, first_len)?;
+        let total = decoded
+            .header
+            .as_ref()
+            .map_or(self.total, |header| header.chunk_count);
+        if let Some(total) = total {
+            if highest >= total {
+                return Err(ChunkError::ChunkCountMismatch {
+                    total,
+                    got: highest,
+                });
+            }
+        }
+        let is_final = decoded.is_last || self.is_terminator(&decoded);
+        if let Some(last) = self.last {
+            if counter > last || (is_final && counter != last) {
+                return Err(ChunkError::UnexpectedChunkNumber {
+                    expected: Some(last),
+                    got: counter,
+                });
+            }
+        }
+        if is_final {
+            self.last = Some(counter);
+        }
+        if let Some(header) = decoded.header {
+            self.apply_header(header);
+        }
+        if counter == 0 {
+            self.first_len = Some(decoded.payload.len());
+        }
+        self.received += decoded.payload.len();
+        self.chunks.insert(counter, decoded.payload.to_vec());
+        self.try_complete()
+    }
+
+    /**
+     * Drops the message, options are kept, the session id of the next
+     * message is taken anew
+     */
+    fn start_over(&mut self) {
+        let mut format = self.format;
+        format.detached_header = false;
+        *self = Dechunker {
+            format,
+            max_chunk_size: self.max_chunk_size,
+            max_message_len: self.max_message_len,
+            ..Default::default()
+        };
+    }
+
+    /**
+     * Pushes the first checked frame of a message found past the start
+     * of frame. Candidates are tried in a loop, the frame pushed decodes,
+     * so `push` doesn't get back here
+     */
+    fn resync(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, ChunkError> {
+        let mut format = self.format;
+        format.detached_header = false;
+        // without both a header can't be told from payload bytes
+        if !format.chunk_length || format.checksum.is_none() {
+            return Err(ChunkError::CrcMismatch);
+        }
+        for start in 1..frame.len() {
+            let bytes = &frame[start..];
+            let Some(len) = format.checked_first_frame(bytes, self.max_message_len) else {
+                continue;
+            };
+            let candidate = &bytes[..len];
+            if format.decode(candidate).is_err() {
+                continue;
+            }
+            trace!(
+                "resynchronized at {} of a {} byte frame",
+                start,
+                frame.len()
+            );
+            self.start_over();
+            return self.push(candidate);
+        }
+        Err(ChunkError::CrcMismatch)
+    }
+
+    /**
+     * Accepts the header delivered apart from the chunks, see `Chunk::header_frame`
+     * and `Chunk::payload_chunks`. Chunk 0 is then expected without header,
+     * so call it before pushing chunk 0. Returns reassembled data when every
+     * chunk arrived before the header
+     */
+    pub fn set_header(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, ChunkError> {
+        let header = self.format.decode_header(frame)?;
+        if self.complete {
+            return Ok(None);
+        }
+        self.check_header(&header)?;
+        if let (Some(total), Some(&highest)) = (header.chunk_count, self.chunks.keys().next_back())
+        {
+            if highest >= total {
+                return Err(ChunkError::ChunkCountMismatch {
+                    total,
+                    got: highest,
+                });
+            }
+        }
+        self.format.detached_header = true;
+        self.apply_header(header);
+        self.try_complete()
+    }
+
+    /**
+     * The terminator sets the top bit of its counter, see `Chunk::with_terminator`
+     */
+    fn is_terminator(&self, decoded: &DecodedFrame) -> bool {
+        self.format.terminator && decoded.counter > 0 && decoded.is_last
+    }
+
+    /**
+     * Declared length against `with_max_message_len()` and, with
+     * `with_chunk_count()`, against the chunk count: chunks after the first
+     * carry at least a byte, with `with_max_chunk_size()` at most a
+     * frame each. Inconsistent counts fail with `LengthMismatch`, got is
+     * the fewest or most bytes the declared chunks can carry
+     */
+    fn check_header(&self, header: &FirstHeader) -> Result<(), ChunkError> {
+        if let Some(max) = self.max_message_len.filter(|&max| header.len > max) {
+            return Err(ChunkError::MessageTooLarge {
+                max,
+                got: header.len,
+            });
+        }
+        let Some(total) = header.chunk_count else {
+            return Ok(());
+        };
+        let data_chunks = total.saturating_sub(self.format.terminator as usize);
+        let fewest = data_chunks.saturating_sub(1);
+        if fewest > header.len {
+            return Err(ChunkError::LengthMismatch {
+                expected: header.len,
+                got: fewest,
+            });
+        }
+        if let Some(max_chunk_size) = self.max_chunk_size {
+            let most = data_chunks.saturating_mul(max_chunk_size);
+            if header.len > most {
+                return Err(ChunkError::LengthMismatch {
+                    expected: header.len,
+                    got: most,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Chunks after the first carry a byte at least, so a counter past the
+     * declared length less the first chunk fails with `CounterOutOfRange`,
+     * `with_max_message_len()` stands for the length until the header arrives.
+     * A hostile counter can't make `missing()` list every counter below it
+     */
+    fn check_counter(
+        &self,
+        counter: usize,
+        len: Option<usize>,
+        first_len: Option<usize>,
+    ) -> Result<(), ChunkError> {
+        let Some(len) = len.or(self.max_message_len) else {
+            return Ok(());
+        };
+        let most = len
+            .saturating_sub(first_len.unwrap_or(0))
+            .saturating_add(self.format.terminator as usize);
+        if counter > most {
+            return Err(ChunkError::CounterOutOfRange);
+        }
+        Ok(())
+    }
+
+    fn apply_header(&mut self, header: FirstHeader) {
+        if let Some(total) = header.chunk_count {
+            self.total = Some(total);
+            self.last.get_or_insert(total - 1);
+        }
+        self.expected_crc = header.message_crc;
+        self.flags = header.flags;
+        self.inflate = header.compressed;
+        self.topic = Some(header.topic);
+        self.len = Some(header.len);
+        self.header_len = Some(header.header_len);
+    }
+
+    /**
+     * Fails with `CounterOutOfRange` when the offset of the counter overflows
+     */
+    fn data_offset(&self, decoded: &DecodedFrame) -> Result<usize, ChunkError> {
+        let counter = decoded.counter;
+        if counter == 0 {
+            return Ok(0);
+        }
+        // every frame of a message has the same prefix, varint counters included
+        let header_len = self.header_len.unwrap_or_else(|| self.format.header_len());
+        // header bytes of the chunks after the first one
+        let repeated_len = if self.format.repeat_header {
+            header_len
+        } else {
+            0
+        };
+        let per_chunk = match (self.max_chunk_size, self.first_len) {
+            (Some(max), _) => {
+                let max = match self.format.alignment {
+                    Some(alignment) if alignment.is_power_of_two() => max & !(alignment - 1),
+                    _ => max,
+                };
+                let checksum_size = self.format.checksum.map_or(0, checksum_width);
+                max.saturating_sub(decoded.prefix_len + checksum_size + repeated_len)
+            }
+            (None, Some(first_len)) => first_len + header_len - repeated_len,
+            (None, None) => decoded.payload.len(),
+        };
+        (counter - 1)
+            .checked_mul(per_chunk)
+            .and_then(|offset| {
+                offset.checked_add((per_chunk + repeated_len).saturating_sub(header_len))
+            })
+            .ok_or(ChunkError::CounterOutOfRange)
+    }
+
+    fn try_complete(&mut self) -> Result<Option<Vec<u8>>, ChunkError> {
+        let len = match self.len {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        if self.received > len {
+            return Err(ChunkError::LengthMismatch {
+                expected: len,
+                got: self.received,
+            });
+        }
+        // counters are unique, so a contiguous run 0..n means nothing is missing
+        let contiguous = self
+            .chunks
+            .keys()
+            .next_back()
+            .is_some_and(|&highest| highest < self.chunks.len());
+        if self.format.terminator {
+            // the terminator completes the message, not reaching the length
+            let terminated = self
+                .last
+                .is_some_and(|last| self.chunks.contains_key(&last));
+            if !terminated || !contiguous {
+                return Ok(None);
+            }
+            if self.received < len {
+                return Err(ChunkError::LengthMismatch {
+                    expected: len,
+                    got: self.received,
+                });
+            }
+        } else if self.received < len || !contiguous {
+            return Ok(None);
+        }
+        let mut data = Vec::with_capacity(len);
+        // payloads are taken out, keys are kept to detect late duplicates
+        for payload in self.chunks.values_mut() {
+            data.append(payload);
+        }
+        self.complete = true;
+        if let Some(expected) = self.expected_crc {
+            if crc32_append(self.drained_crc, &data) != expected {
+                return Err(ChunkError::MessageCrcMismatch);
+            }
+        }
+        #[cfg(feature = "compress")]
+        if self.inflate {
+            return crate::compress::inflate(&data, self.max_message_len).map(Some);
+        }
+        Ok(Some(data))
+    }
+}
+
+/**
+ * `Dechunker` writing the data into a sink closure as it arrives in order,
+ * see `Dechunker::with_sink`. Compressed messages reach the sink at once,
+ * inflated on completion
+ */
+pub struct SinkDechunker<F> {
+    dechunker: Dechunker,
+    sink: F,
+}
+
+impl<F> SinkDechunker<F>
+where
+    F: FnMut(&[u8]) -> Result<(), ChunkError>,
+{
+    /**
+     * Accepts a single wire frame like `Dechunker::push` and calls the sink
+     * with the run of bytes it makes contiguous, if any. Returns the data
+     * length once complete, after checking the sink got the declared length.
+     * Errors of the sink are returned as they are, the bytes it was called
+     * with aren't handed out again. An abort frame fails with `Aborted`,
+     * what the sink received so far is for the caller to discard
+     */
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<usize>, ChunkError> {
+        let dechunker = &mut self.dechunker;
+        let Some(rest) = dechunker.push(frame)? else {
+            if let Some((_, run)) = dechunker.drain_contiguous() {
+                (self.sink)(run)?;
+            }
+            return Ok(None);
+        };
+        if !rest.is_empty() {
+            (self.sink)(&rest)?;
+        }
+        let total = dechunker.drained + rest.len();
+        match dechunker.len {
+            Some(len) if len != total && !dechunker.inflate => Err(ChunkError::LengthMismatch {
+                expected: len,
+                got: total,
+            }),
+            _ => Ok(Some(total)),
+        }
+    }
+
+    /**
+     * Underlying `Dechunker`, for its progress and missing chunks
+     */
+    pub fn dechunker(&self) -> &Dechunker {
+        &self.dechunker
+    }
+
+    pub fn into_sink(self) -> F {
+        self.sink
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crc::crc32;
+    use crate::{Chunk, Crc32, FLAG_CRC, FLAG_HAS_SESSION_ID, FLAG_MESSAGE_CRC};
+    use alloc::vec;
+
+    fn frames(chunk: Chunk) -> Vec<Vec<u8>> {
+        let frames = chunk.to_frames();
+        assert_eq!(frames.len(), chunk.total_chunks());
+        frames
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut dechunker = Dechunker::new();
+        let mut result = None;
+        for frame in frames(Chunk::new(250, 0x10, &data)) {
+            result = dechunker.push(&frame).unwrap();
+        }
+        assert!(dechunker.is_complete());
+        assert_eq!(dechunker.topic(), Some(0x10));
+        assert_eq!(dechunker.topic_as::<u16>().unwrap(), Some(0x10));
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_topic_as() {
+        let data = [0; 10];
+        let mut dechunker = Dechunker::new();
+        assert_eq!(dechunker.topic_as::<core::num::NonZeroU8>().unwrap(), None);
+        for frame in frames(Chunk::new(250, 0, &data)) {
+            dechunker.push(&frame).unwrap();
+        }
+        assert!(matches!(
+            dechunker.topic_as::<core::num::NonZeroU8>(),
+            Err(ChunkError::InvalidTopic(0))
+        ));
+    }
+
+    #[test]
+    fn test_reassemble_with_checksum() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut frames = frames(Chunk::new(250, 0x10, &data).with_checksum(&Crc32));
+        let mut dechunker = Dechunker::new().with_checksum(&Crc32);
+        let last = frames.pop().unwrap();
+        for frame in &frames {
+            assert!(dechunker.push(frame).unwrap().is_none());
+        }
+        assert_eq!(dechunker.push(&last).unwrap().unwrap(), data);
+
+        let mut dechunker = Dechunker::new().with_crc();
+        assert!(matches!(
+            dechunker.push(&frames[0]),
+            Err(ChunkError::CrcMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut dechunker = Dechunker::new();
+        let mut frames = frames(Chunk::new(250, 0x10, &data));
+        frames.reverse();
+        let last = frames.pop().unwrap();
+        for frame in frames {
+            assert!(dechunker.push(&frame).unwrap().is_none());
+        }
+        assert!(!dechunker.is_complete());
+        assert_eq!(dechunker.push(&last).unwrap().unwrap(), data);
+    }
+
+    #[test]
+    fn test_reassemble_with_crc() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut dechunker = Dechunker::new().with_crc();
+        let mut frames = frames(Chunk::new(250, 0x10, &data).with_crc());
+        for frame in &frames {
+            assert!(frame.len() <= 250);
+        }
+        frames[1][10] ^= 0xFF;
+        assert!(matches!(
+            dechunker.push(&frames[1]),
+            Err(ChunkError::CrcMismatch)
+        ));
+        frames[1][10] ^= 0xFF;
+        let mut result = None;
+        for frame in &frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_reassemble_empty() {
+        let mut dechunker = Dechunker::new();
+        let frames = frames(Chunk::new(250, 0x10, &[]));
+        assert_eq!(frames.len(), 1);
+        assert_eq!(dechunker.push(&frames[0]).unwrap(), Some(vec![]));
+        assert!(dechunker.is_complete());
+    }
+
+    #[test]
+    fn test_reassemble_meta_width() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut dechunker = Dechunker::new().with_meta_width(MetaWidth::U16);
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        let mut result = None;
+        for frame in frames(chunk) {
+            result = dechunker.push(&frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_reassemble_separate_widths() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut dechunker = Dechunker::new()
+            .with_length_width(LengthWidth::U32)
+            .with_counter_width(CounterWidth::U16)
+            .with_chunk_count();
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_length_width(LengthWidth::U32)
+            .with_counter_width(CounterWidth::U16)
+            .with_chunk_count();
+        let mut result = None;
+        for frame in frames(chunk) {
+            result = dechunker.push(&frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_reassemble_varint() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        // more than 127 chunks, the counter takes 2 varint bytes in every frame
+        let chunk = Chunk::new(6, 0x10, &data).with_varint();
+        assert_eq!(chunk.min_chunk_size(), 6);
+        assert_eq!(chunk.chunk_capacity(), 4);
+        assert_eq!(chunk.total_chunks(), 251);
+        let mut dechunker = Dechunker::new().with_varint().with_max_chunk_size(6);
+        let wire = frames(chunk);
+        assert_eq!(wire[1], [0x81, 0x00, 1, 2, 3, 4]);
+        dechunker.push(&wire[0]).unwrap();
+        let frame = dechunker.decode_frame(&wire[200]).unwrap();
+        assert_eq!(frame.counter, 200);
+        assert_eq!(frame.data_offset, 1 + 199 * 4);
+        let mut result = None;
+        for frame in wire.iter().rev() {
+            result = dechunker.push(frame).unwrap().or(result);
+        }
+        assert_eq!(result.unwrap(), data);
+
+        let chunk = Chunk::new(40, 0x10, &data)
+            .with_varint()
+            .with_crc()
+            .with_last_flag()
+            .with_chunk_length()
+            .with_session_id(7)
+            .with_message_crc()
+            .with_header_flags()
+            .with_version(2);
+        let mut dechunker = Dechunker::new()
+            .with_varint()
+            .with_crc()
+            .with_last_flag()
+            .with_chunk_length()
+            .with_session_ids()
+            .with_message_crc()
+            .with_header_flags()
+            .with_version(2);
+        let mut result = None;
+        for frame in frames(chunk) {
+            let decoded = dechunker.decode_frame(&frame).unwrap();
+            assert_eq!(decoded.session_id, Some(7));
+            result = dechunker.push(&frame).unwrap();
+            assert_eq!(decoded.is_last, result.is_some());
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_reassemble_big_endian() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U32)
+            .with_endianness(Endianness::Big);
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U32)
+            .with_endianness(Endianness::Big);
+        let mut result = None;
+        for frame in frames(chunk) {
+            result = dechunker.push(&frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_reassemble_versioned() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let frames = frames(Chunk::new(250, 0x10, &data).with_version(1));
+        let mut dechunker = Dechunker::new().with_version(1);
+        let mut result = None;
+        for frame in &frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+
+        let mut dechunker = Dechunker::new().with_version(2);
+        assert!(matches!(
+            dechunker.push(&frames[0]),
+            Err(ChunkError::UnsupportedVersion(1))
+        ));
+    }
+
+    #[test]
+    fn test_header_flags() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let frames = frames(
+            Chunk::new(250, 0x10, &data)
+                .with_version(1)
+                .with_crc()
+                .with_session_id(3)
+                .with_header_flags(),
+        );
+        let mut dechunker = Dechunker::new()
+            .with_version(1)
+            .with_crc()
+            .with_session_ids()
+            .with_header_flags();
+        let mut result = None;
+        for frame in &frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+        assert_eq!(
+            dechunker.flags().unwrap().bits(),
+            FLAG_CRC | FLAG_HAS_SESSION_ID
+        );
+
+        let mut dechunker = Dechunker::new()
+            .with_version(1)
+            .with_crc()
+            .with_session_ids()
+            .with_message_crc()
+            .with_header_flags();
+        assert!(matches!(
+            dechunker.push(&frames[0]),
+            Err(ChunkError::FlagsMismatch { expected, got })
+                if expected == FLAG_CRC | FLAG_HAS_SESSION_ID | FLAG_MESSAGE_CRC
+                    && got == FLAG_CRC | FLAG_HAS_SESSION_ID
+        ));
+    }
+
+    #[test]
+    fn test_last_flag() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut frames = frames(Chunk::new(250, 0x10, &data).with_last_flag());
+        let mut dechunker = Dechunker::new().with_last_flag();
+        let last = frames.pop().unwrap();
+        for frame in &frames {
+            assert!(!dechunker.decode_frame(frame).unwrap().is_last);
+            dechunker.push(frame).unwrap();
+        }
+        assert!(matches!(
+            dechunker.finish(),
+            Err(ChunkError::MissingFinalChunk)
+        ));
+        let frame = dechunker.decode_frame(&last).unwrap();
+        assert!(frame.is_last);
+        assert_eq!(frame.counter, 4);
+        assert_eq!(frame.topic, 0x10);
+        assert_eq!(dechunker.push(&last).unwrap().unwrap(), data);
+        assert!(dechunker.finish().is_ok());
+
+        let mut dechunker = Dechunker::new().with_last_flag();
+        dechunker.push(&last).unwrap();
+        dechunker.push(&frames[1]).unwrap();
+        assert!(matches!(
+            dechunker.finish(),
+            Err(ChunkError::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_frame() {
+        let data = [1, 2, 3];
+        let frames = frames(Chunk::new(250, 0x10, &data));
+        let frame = Dechunker::new().decode_frame(&frames[0]).unwrap();
+        assert!(frame.is_first && frame.is_last);
+        assert_eq!(frame.payload, data);
+        assert_eq!(frame.data_offset, 0);
+    }
+
+    #[test]
+    fn test_decode_frame_max_counter() {
+        let mut frame = [0xff; 12];
+        frame[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            Dechunker::new().decode_frame(&frame),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+        assert!(matches!(
+            Dechunker::new()
+                .with_max_chunk_size(250)
+                .decode_frame(&frame),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn test_data_offset() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        let sent: Vec<_> = chunk.frames().map(|frame| frame.data_offset).collect();
+        let frames = frames(chunk);
+
+        let dechunker = Dechunker::new().with_crc().with_max_chunk_size(250);
+        let mut end = 0;
+        for (frame, &offset) in frames.iter().rev().zip(sent.iter().rev()) {
+            let decoded = dechunker.decode_frame(frame).unwrap();
+            assert_eq!(decoded.data_offset, offset);
+            assert_eq!(
+                decoded.payload,
+                &data[offset..offset + decoded.payload.len()]
+            );
+            end = end.max(offset + decoded.payload.len());
+        }
+        assert_eq!(end, data.len());
+        let mut next = 0;
+        for frame in &frames {
+            let decoded = dechunker.decode_frame(frame).unwrap();
+            assert_eq!(decoded.data_offset, next);
+            next += decoded.payload.len();
+        }
+        assert_eq!(next, data.len());
+
+        // learned from the first chunk
+        let mut dechunker = Dechunker::new().with_crc();
+        dechunker.push(&frames[0]).unwrap();
+        for (frame, &offset) in frames.iter().zip(&sent) {
+            assert_eq!(dechunker.decode_frame(frame).unwrap().data_offset, offset);
+        }
+    }
+
+    #[test]
+    fn test_session_ids() {
+        let first: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let second: Vec<u8> = (0..600).map(|i| (i * 3) as u8).collect();
+        let first_frames = frames(Chunk::new(250, 0x10, &first).with_session_id(1));
+        let second_frames = frames(Chunk::new(250, 0x10, &second).with_session_id(2));
+        let mut dechunkers = [
+            Dechunker::new().with_session_ids(),
+            Dechunker::new().with_session_ids(),
+        ];
+        let mut results = Vec::new();
+        let interleaved = [
+            &first_frames[0],
+            &second_frames[0],
+            &first_frames[1],
+            &second_frames[1],
+            &second_frames[2],
+            &first_frames[2],
+            &first_frames[3],
+            &first_frames[4],
+        ];
+        for frame in interleaved {
+            let frame_id = dechunkers[0].decode_frame(frame).unwrap().session_id;
+            for dechunker in &mut dechunkers {
+                match dechunker.push(frame) {
+                    Ok(Some(data)) => results.push((frame_id, data)),
+                    Ok(None) => {}
+                    Err(ChunkError::SessionMismatch { .. }) => continue,
+                    Err(err) => panic!("{}", err),
+                }
+                break;
+            }
+        }
+        assert_eq!(results, [(Some(2), second), (Some(1), first)]);
+        assert_eq!(dechunkers[0].session_id(), Some(1));
+        assert!(matches!(
+            dechunkers[0].push(&second_frames[0]),
+            Err(ChunkError::SessionMismatch {
+                expected: 1,
+                got: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_message_crc() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data).with_message_crc();
+        assert_eq!(chunk.header().len(), 1 + 8 + 4);
+        assert_eq!(chunk.header()[9..], crc32(&data).to_le_bytes());
+        let frames = frames(chunk);
+        let mut dechunker = Dechunker::new().with_message_crc();
+        let mut result = None;
+        for frame in &frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+
+        // chunks swapped in flight pass the per-frame checks but not the message CRC
+        let mut swapped = frames.clone();
+        let (a, b) = (swapped[1][8..].to_vec(), swapped[2][8..].to_vec());
+        swapped[1][8..].copy_from_slice(&b);
+        swapped[2][8..].copy_from_slice(&a);
+        let mut dechunker = Dechunker::new().with_message_crc();
+        let results: Vec<_> = swapped.iter().map(|frame| dechunker.push(frame)).collect();
+        assert!(matches!(
+            results.last(),
+            Some(Err(ChunkError::MessageCrcMismatch))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_length() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_chunk_length()
+            .with_session_id(7);
+        let frames = frames(chunk);
+        let mut dechunker = Dechunker::new().with_chunk_length().with_session_ids();
+        assert!(matches!(
+            dechunker.push(&frames[1][..100]),
+            Err(ChunkError::TruncatedChunk {
+                expected: 232,
+                got: 82
+            })
+        ));
+        let mut result = None;
+        for frame in &frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(dechunker.session_id(), Some(7));
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_duplicate_chunk() {
+        let data = vec![0; 1000];
+        let mut dechunker = Dechunker::new();
+        let frames = frames(Chunk::new(250, 0x10, &data));
+        assert!(dechunker.push(&frames[1]).unwrap().is_none());
+        assert!(dechunker.push(&frames[1]).unwrap().is_none());
+        assert_eq!(dechunker.received_count(), 1);
+        let mut result = None;
+        for frame in frames.iter().chain(&frames) {
+            if let Some(data) = dechunker.push(frame).unwrap() {
+                assert!(result.is_none());
+                result = Some(data);
+            }
+        }
+        assert_eq!(result.unwrap(), data);
+        assert_eq!(dechunker.received_count(), 5);
+    }
+
+    #[test]
+    fn test_drain_contiguous() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let frames = frames(Chunk::new(250, 0x10, &data).with_message_crc());
+        let mut dechunker = Dechunker::new().with_message_crc();
+        assert!(dechunker.drain_contiguous().is_none());
+        dechunker.push(&frames[1]).unwrap();
+        assert!(dechunker.drain_contiguous().is_none());
+        dechunker.push(&frames[0]).unwrap();
+        dechunker.push(&frames[3]).unwrap();
+        let (offset, run) = dechunker.drain_contiguous().unwrap();
+        assert_eq!(offset, 0);
+        assert_eq!(run, &data[..run.len()]);
+        let drained = run.len();
+        assert!(dechunker.drain_contiguous().is_none());
+        dechunker.push(&frames[2]).unwrap();
+        let (offset, run) = dechunker.drain_contiguous().unwrap();
+        assert_eq!(offset, drained);
+        assert_eq!(run, &data[drained..drained + run.len()]);
+        let drained = drained + run.len();
+        let rest = dechunker.push(&frames[4]).unwrap().unwrap();
+        assert_eq!(rest, data[drained..]);
+        assert!(dechunker.is_complete());
+    }
+
+    #[test]
+    fn test_with_sink() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk_frames = frames(Chunk::new(250, 0x10, &data).with_message_crc());
+        let mut written = Vec::new();
+        let mut runs = 0;
+        let mut dechunker = Dechunker::new().with_message_crc().with_sink(|run: &[u8]| {
+            written.extend_from_slice(run);
+            runs += 1;
+            Ok(())
+        });
+        for i in [1, 0, 3, 2] {
+            assert_eq!(dechunker.push(&chunk_frames[i]).unwrap(), None);
+        }
+        assert_eq!(dechunker.dechunker().drained, 955);
+        assert_eq!(dechunker.push(&chunk_frames[4]).unwrap(), Some(1000));
+        assert_eq!(dechunker.push(&chunk_frames[4]).unwrap(), None);
+        drop(dechunker);
+        assert_eq!(written, data);
+        // 0 and 1, 2 and 3, then the final chunk
+        assert_eq!(runs, 3);
+
+        let mut dechunker = Dechunker::new().with_sink(|_: &[u8]| Err(ChunkError::UnexpectedEof));
+        assert_eq!(dechunker.push(&chunk_frames[1]).unwrap(), None);
+        assert!(matches!(
+            dechunker.push(&chunk_frames[0]),
+            Err(ChunkError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_resync() {
+        let old = [7; 1000];
+        let data: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        fn options(chunk: Chunk) -> Chunk {
+            chunk.with_header_flags().with_crc().with_chunk_length()
+        }
+        let old_frames = options(Chunk::new(250, 0x10, &old)).to_frames();
+        let new_frames = options(Chunk::new(250, 0x11, &data)).to_frames();
+        let mut dechunker = Dechunker::new()
+            .with_header_flags()
+            .with_crc()
+            .with_chunk_length();
+        dechunker.push(&old_frames[0]).unwrap();
+        dechunker.push(&old_frames[1]).unwrap();
+
+        // the rest of the old message got lost together with the boundary
+        let mut glued = old_frames[2][..100].to_vec();
+        glued.extend_from_slice(&new_frames[0]);
+        assert_eq!(dechunker.push(&glued).unwrap(), None);
+        assert_eq!(dechunker.topic(), Some(0x11));
+        assert_eq!(dechunker.received_count(), 1);
+        dechunker.push(&new_frames[1]).unwrap();
+        assert_eq!(dechunker.push(&new_frames[2]).unwrap().unwrap(), data);
+
+        let mut dechunker = Dechunker::new().with_crc();
+        assert!(matches!(
+            dechunker.push(&glued[..150]),
+            Err(ChunkError::CrcMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_resync_large_corrupt_frame() {
+        let mut frame = vec![0; 64 * 1024];
+        frame[0] = 1;
+        for mut dechunker in [
+            Dechunker::new().with_crc(),
+            Dechunker::new().with_crc().with_chunk_length(),
+        ] {
+            assert!(matches!(
+                dechunker.push(&frame),
+                Err(ChunkError::CrcMismatch)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_resync_keeps_message() {
+        let data = [0; 1000];
+        for chunk_length in [false, true] {
+            let mut chunk = Chunk::new(250, 0x10, &data).with_crc();
+            let mut dechunker = Dechunker::new().with_crc();
+            if chunk_length {
+                chunk = chunk.with_chunk_length();
+                dechunker = dechunker.with_chunk_length();
+            }
+            let chunk_frames = frames(chunk);
+            dechunker.push(&chunk_frames[0]).unwrap();
+            dechunker.push(&chunk_frames[1]).unwrap();
+            let mut corrupt = chunk_frames[2].clone();
+            corrupt[100] ^= 1;
+            assert!(matches!(
+                dechunker.push(&corrupt),
+                Err(ChunkError::CrcMismatch)
+            ));
+            assert_eq!(dechunker.received_count(), 2);
+            assert_eq!(dechunker.topic(), Some(0x10));
+            for frame in &chunk_frames[2..4] {
+                dechunker.push(frame).unwrap();
+            }
+            assert_eq!(dechunker.push(&chunk_frames[4]).unwrap().unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_progress() {
+        let data = vec![0; 1000];
+        let frames = frames(Chunk::new(250, 0x10, &data));
+        let mut dechunker = Dechunker::new();
+        dechunker.push(&frames[1]).unwrap();
+        assert_eq!(dechunker.progress(), 0.0);
+        assert_eq!(dechunker.bytes_remaining(), None);
+        dechunker.push(&frames[0]).unwrap();
+        let received = 250 - 8 + 250 - 8 * 2 - 1;
+        assert_eq!(dechunker.bytes_remaining(), Some(1000 - received));
+        assert_eq!(dechunker.progress(), received as f32 / 1000.0);
+        for frame in &frames[2..] {
+            dechunker.push(frame).unwrap();
+        }
+        assert_eq!(dechunker.bytes_remaining(), Some(0));
+        assert_eq!(dechunker.progress(), 1.0);
+
+        let empty = Chunk::new(250, 0x10, &[]).to_frames();
+        let mut dechunker = Dechunker::new();
+        dechunker.push(&empty[0]).unwrap();
+        assert_eq!(dechunker.progress(), 1.0);
+    }
+
+    #[test]
+    fn test_max_message_len() {
+        let data = [7; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let first = chunk.frame_vec(0, true).unwrap();
+        let mut dechunker = Dechunker::new().with_max_message_len(999);
+        assert!(matches!(
+            dechunker.push(&first),
+            Err(ChunkError::MessageTooLarge {
+                max: 999,
+                got: 1000
+            })
+        ));
+        assert!(dechunker.topic().is_none());
+        assert!(matches!(
+            dechunker.set_header(&chunk.header_frame()),
+            Err(ChunkError::MessageTooLarge { .. })
+        ));
+        let mut dechunker = Dechunker::new().with_max_message_len(1000);
+        let mut result = None;
+        for frame in frames(chunk) {
+            result = dechunker.push(&frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+
+        // header declaring 21 chunks for 10 bytes
+        let chunk = Chunk::new(250, 0x10, &data[..10])
+            .with_chunk_count()
+            .with_varint();
+        let mut frame = chunk.frame_vec(0, true).unwrap();
+        assert_eq!(frame[..4], [0, 0x10, 10, 0]);
+        frame[3] = 20;
+        let mut dechunker = Dechunker::new().with_chunk_count().with_varint();
+        assert!(matches!(
+            dechunker.push(&frame),
+            Err(ChunkError::LengthMismatch {
+                expected: 10,
+                got: 20
+            })
+        ));
+
+        // 3 chunks of 250 bytes can't carry 1000
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_chunk_count()
+            .with_varint();
+        let mut frame = chunk.frame_vec(0, true).unwrap();
+        frame[4] = 2;
+        let mut dechunker = Dechunker::new()
+            .with_chunk_count()
+            .with_varint()
+            .with_max_chunk_size(250);
+        assert!(matches!(
+            dechunker.push(&frame),
+            Err(ChunkError::LengthMismatch {
+                expected: 1000,
+                got: 750
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_first_chunk() {
+        let data = [7; 1000];
+        let chunk_frames = frames(Chunk::new(250, 0x10, &data).with_crc());
+        let mut dechunker = Dechunker::new().with_crc();
+        dechunker.push(&chunk_frames[1]).unwrap();
+        // chunk 0 cut after its counter, with a CRC matching what is left
+        let mut truncated = chunk_frames[0][..8].to_vec();
+        truncated.extend_from_slice(&crate::crc16(&truncated).to_le_bytes());
+        assert!(matches!(
+            dechunker.push(&truncated),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+        assert_eq!(dechunker.received_count(), 1);
+
+        let mut dechunker = Dechunker::new();
+        dechunker.push(&chunk_frames[1][..250 - 2]).unwrap();
+        assert!(matches!(
+            dechunker.push(&chunk_frames[0][..8]),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+        assert_eq!(dechunker.received_count(), 1);
+    }
+
+    #[test]
+    fn test_abort() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_crc()
+            .with_session_id(1)
+            .with_repeat_header();
+        let mut dechunker = Dechunker::new()
+            .with_crc()
+            .with_session_ids()
+            .with_repeat_header();
+        let chunk_frames = frames(chunk.clone());
+        for frame in &chunk_frames[..3] {
+            assert_eq!(dechunker.push(frame).unwrap(), None);
+        }
+        let mut abort = [0; 16];
+        let other = Chunk::new(250, 0x10, &data).with_crc().with_session_id(2);
+        let len = other.encode_abort(&mut abort).unwrap();
+        assert!(matches!(
+            dechunker.push(&abort[..len]),
+            Err(ChunkError::SessionMismatch {
+                expected: 1,
+                got: 2
+            })
+        ));
+        let len = chunk.encode_abort(&mut abort).unwrap();
+        assert!(matches!(
+            dechunker.decode_frame(&abort[..len]),
+            Err(ChunkError::Aborted)
+        ));
+        assert!(matches!(
+            dechunker.push(&abort[..len]),
+            Err(ChunkError::Aborted)
+        ));
+        assert_eq!(dechunker.topic(), None);
+        assert_eq!(dechunker.session_id(), None);
+
+        let mut result = None;
+        for frame in frames(other.with_repeat_header()) {
+            result = dechunker.push(&frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+        assert_eq!(dechunker.session_id(), Some(2));
+    }
+
+    #[test]
+    fn test_abort_out_of_band() {
+        // chunk 0 carrying the bytes of the former in-band marker
+        let mut data = b"RT".to_vec();
+        data.resize(66, b'.');
+        let chunk = Chunk::new(5, 0x41, &data).with_varint();
+        let chunk_frames = frames(chunk.clone());
+        assert_eq!(chunk_frames[0], [0, b'A', b'B', b'R', b'T']);
+        let mut dechunker = Dechunker::new().with_varint();
+        let mut result = None;
+        for frame in &chunk_frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+
+        let mut abort = [0; 16];
+        let len = chunk.encode_abort(&mut abort).unwrap();
+        assert_eq!(len, 9);
+        let mut dechunker = Dechunker::new().with_varint();
+        dechunker.push(&chunk_frames[1]).unwrap();
+        assert!(matches!(
+            dechunker.push(&abort[..len]),
+            Err(ChunkError::Aborted)
+        ));
+        assert_eq!(dechunker.received_count(), 0);
+
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_last_flag();
+        assert_eq!(chunk.abort_counter(), 0x7FFF);
+        let len = chunk.encode_abort(&mut abort).unwrap();
+        assert_eq!(abort[..len], [0xFF, 0x7F]);
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U16)
+            .with_last_flag();
+        assert!(matches!(
+            dechunker.push(&abort[..len]),
+            Err(ChunkError::Aborted)
+        ));
+    }
+
+    #[test]
+    fn test_allowed_topics() {
+        let data = vec![0; 1000];
+        let mut dechunker = Dechunker::new();
+        dechunker.allowed_topics(&[0x10, 0x20]);
+        let other = frames(Chunk::new(250, 0x30, &data));
+        assert!(matches!(
+            dechunker.push(&other[0]),
+            Err(ChunkError::UnexpectedTopic(0x30))
+        ));
+        assert_eq!(dechunker.received_count(), 0);
+        let frames = frames(Chunk::new(250, 0x20, &data));
+        let mut result = None;
+        for frame in &frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_missing() {
+        let data = vec![0; 1000];
+        let frames = frames(Chunk::new(250, 0x10, &data).with_last_flag());
+        let mut dechunker = Dechunker::new().with_last_flag();
+        assert!(dechunker.missing().is_empty());
+        dechunker.push(&frames[2]).unwrap();
+        assert_eq!(dechunker.missing(), [0, 1]);
+        dechunker.push(&frames[4]).unwrap();
+        assert_eq!(dechunker.missing(), [0, 1, 3]);
+        for frame in &frames {
+            dechunker.push(frame).unwrap();
+        }
+        assert!(dechunker.missing().is_empty());
+    }
+
+    #[test]
+    fn test_counter_past_max_message_len() {
+        let mut dechunker = Dechunker::new().with_max_message_len(1000);
+        let mut frame = [7; 12];
+        frame[..8].copy_from_slice(&(1u64 << 28).to_le_bytes());
+        assert!(matches!(
+            dechunker.push(&frame),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+        frame[..8].copy_from_slice(&1000u64.to_le_bytes());
+        assert_eq!(dechunker.push(&frame).unwrap(), None);
+        assert_eq!(dechunker.missing().len(), 1000);
+    }
+
+    #[test]
+    fn test_counter_past_len() {
+        let data = vec![0; 1000];
+        let frames = frames(Chunk::new(250, 0x10, &data));
+        let mut dechunker = Dechunker::new();
+        dechunker.push(&frames[0]).unwrap();
+        // 767 bytes after the first chunk, a byte each at least
+        for counter in [768, 1 << 28, usize::MAX] {
+            let mut frame = [7; 12];
+            frame[..8].copy_from_slice(&(counter as u64).to_le_bytes());
+            assert!(matches!(
+                dechunker.push(&frame),
+                Err(ChunkError::CounterOutOfRange)
+            ));
+        }
+        assert!(dechunker.missing().is_empty());
+        dechunker.push(&frames[2]).unwrap();
+        assert_eq!(dechunker.missing(), [1]);
+
+        // a late first chunk declaring less data than the counters received
+        let mut dechunker = Dechunker::new();
+        dechunker.push(&frames[4]).unwrap();
+        let short = self::frames(Chunk::new(250, 0x10, &[0; 3]));
+        assert!(matches!(
+            dechunker.push(&short[0]),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn test_alignment() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_crc()
+            .with_session_id(5)
+            .with_alignment(16);
+        let mut chunk_frames = frames(chunk);
+        chunk_frames.reverse();
+        let dechunker = Dechunker::new()
+            .with_crc()
+            .with_session_ids()
+            .with_alignment(16)
+            .with_max_chunk_size(250);
+        let offsets: Vec<usize> = chunk_frames
+            .iter()
+            .map(|frame| dechunker.decode_frame(frame).unwrap().data_offset)
+            .collect();
+        assert_eq!(offsets, [899, 672, 445, 218, 0]);
+        let mut dechunker = dechunker;
+        let mut result = None;
+        for frame in &chunk_frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+
+        let mut misaligned = chunk_frames[0].clone();
+        misaligned.push(0);
+        assert!(matches!(
+            Dechunker::new().with_alignment(16).push(&misaligned),
+            Err(ChunkError::LengthMismatch {
+                expected: 144,
+                got: 129
+            })
+        ));
+    }
+
+    #[test]
+    fn test_terminator() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let frames = frames(Chunk::new(250, 0x10, &data).with_terminator());
+        assert!(frames[5].len() == 8);
+        let mut dechunker = Dechunker::new().with_terminator();
+        for frame in &frames[..5] {
+            assert!(dechunker.push(frame).unwrap().is_none());
+        }
+        assert!(!dechunker.is_complete());
+        assert!(dechunker.decode_frame(&frames[5]).unwrap().is_last);
+        assert_eq!(dechunker.push(&frames[5]).unwrap(), Some(data.clone()));
+
+        let mut dechunker = Dechunker::new().with_terminator();
+        dechunker.push(&frames[5]).unwrap();
+        assert!(matches!(
+            dechunker.finish(),
+            Err(ChunkError::LengthMismatch { .. })
+        ));
+        assert_eq!(dechunker.missing(), [0, 1, 2, 3, 4]);
+        dechunker.push(&frames[0]).unwrap();
+        dechunker.push(&frames[1]).unwrap();
+        let mut early = frames[3][..8].to_vec();
+        early[7] |= 0x80;
+        assert!(matches!(
+            dechunker.push(&early),
+            Err(ChunkError::UnexpectedChunkNumber { .. })
+        ));
+
+        let mut dechunker = Dechunker::new().with_terminator();
+        for frame in &frames[..4] {
+            dechunker.push(frame).unwrap();
+        }
+        assert!(matches!(
+            dechunker.finish(),
+            Err(ChunkError::MissingFinalChunk)
+        ));
+
+        let empty = Chunk::new(250, 0x10, &[]).with_terminator().to_frames();
+        let mut dechunker = Dechunker::new().with_terminator();
+        assert!(dechunker.push(&empty[0]).unwrap().is_none());
+        assert_eq!(dechunker.push(&empty[1]).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_terminator_without_header_flags() {
+        let data: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let flagged = frames(
+            Chunk::new(250, 0x10, &data)
+                .with_terminator()
+                .with_header_flags(),
+        );
+        let plain = frames(Chunk::new(250, 0x10, &data).with_terminator());
+        // only the header differs, the terminator frame is the same flagged counter
+        assert_eq!(flagged[3], plain[3]);
+        assert_eq!(plain[3], (3u64 | 1 << 63).to_le_bytes());
+
+        let mut dechunker = Dechunker::new().with_terminator();
+        for frame in &plain[..3] {
+            assert!(dechunker.push(frame).unwrap().is_none());
+        }
+        // an empty frame without the flag isn't taken for the terminator
+        let bare = 3u64.to_le_bytes();
+        assert!(!dechunker.decode_frame(&bare).unwrap().is_last);
+        let frame = dechunker.decode_frame(&plain[3]).unwrap();
+        assert!(frame.is_last && frame.payload.is_empty());
+        assert_eq!(dechunker.push(&plain[3]).unwrap(), Some(data));
+    }
+
+    #[test]
+    fn test_repeat_header() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let frames = frames(Chunk::new(250, 0x10, &data).with_repeat_header());
+        let mut dechunker = Dechunker::new().with_repeat_header();
+        // the short final chunk alone can't tell its offset
+        for frame in &frames[..4] {
+            let decoded = dechunker.decode_frame(frame).unwrap();
+            assert_eq!(decoded.topic, 0x10);
+            assert_eq!(decoded.data_offset, decoded.counter * (250 - 8 - 9));
+        }
+        dechunker.push(&frames[3]).unwrap();
+        assert_eq!(dechunker.topic(), Some(0x10));
+        assert_eq!(dechunker.missing(), [0, 1, 2]);
+        let mut result = None;
+        for frame in &frames[..3] {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert!(result.is_none());
+        assert_eq!(dechunker.push(&frames[4]).unwrap(), Some(data));
+    }
+
+    #[test]
+    fn test_set_header() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc().with_message_crc();
+        let header = chunk.header_frame();
+        assert_eq!(header.len(), chunk.header().len() + 2);
+        assert_eq!(header[..chunk.header().len()], *chunk.header());
+        let payloads: Vec<Vec<u8>> = chunk.payload_chunks().collect();
+        assert_eq!(payloads.len(), chunk.total_chunks());
+        assert_eq!(payloads[0].len() + chunk.header().len(), 250);
+
+        let mut dechunker = Dechunker::new().with_crc().with_message_crc();
+        dechunker.set_header(&header).unwrap();
+        assert_eq!(dechunker.topic(), Some(0x10));
+        let mut result = None;
+        for frame in &payloads {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+
+        let mut dechunker = Dechunker::new().with_crc().with_message_crc();
+        for frame in &payloads[1..] {
+            assert!(dechunker.push(frame).unwrap().is_none());
+        }
+        assert!(dechunker.set_header(&header).unwrap().is_none());
+        assert_eq!(dechunker.push(&payloads[0]).unwrap().unwrap(), data);
+
+        let mut corrupted = header.clone();
+        corrupted[1] ^= 1;
+        assert!(matches!(
+            Dechunker::new().with_crc().set_header(&corrupted),
+            Err(ChunkError::CrcMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_chunk_count() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_chunk_count();
+        assert_eq!(chunk.header(), [0x10, 0xE8, 0x03, 4, 0]);
+        let frames = frames(chunk);
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U16)
+            .with_chunk_count();
+        assert_eq!(
+            dechunker.decode_frame(&frames[3]).unwrap().total_chunks,
+            None
+        );
+        dechunker.push(&frames[0]).unwrap();
+        assert_eq!(dechunker.missing(), [1, 2, 3, 4]);
+        let frame = dechunker.decode_frame(&frames[4]).unwrap();
+        assert_eq!(frame.total_chunks, Some(5));
+        assert!(frame.is_last);
+        dechunker.push(&frames[1]).unwrap();
+        assert!(matches!(
+            dechunker.finish(),
+            Err(ChunkError::MissingFinalChunk)
+        ));
+
+        let mut frame = frames[1].clone();
+        frame[0] = 5;
+        assert!(matches!(
+            dechunker.push(&frame),
+            Err(ChunkError::ChunkCountMismatch { total: 5, got: 5 })
+        ));
+        for frame in &frames[2..] {
+            dechunker.push(frame).unwrap();
+        }
+        assert!(dechunker.is_complete());
+    }
+
+    #[test]
+    fn test_chunk_count_late_header() {
+        let data = vec![0; 1000];
+        let short = frames(
+            Chunk::new(250, 0x10, &data[..500])
+                .with_meta_width(MetaWidth::U16)
+                .with_chunk_count(),
+        );
+        let long = frames(
+            Chunk::new(250, 0x10, &data)
+                .with_meta_width(MetaWidth::U16)
+                .with_chunk_count(),
+        );
+        let mut dechunker = Dechunker::new()
+            .with_meta_width(MetaWidth::U16)
+            .with_chunk_count();
+        dechunker.push(&long[3]).unwrap();
+        assert!(matches!(
+            dechunker.push(&short[0]),
+            Err(ChunkError::ChunkCountMismatch { total: 3, got: 3 })
+        ));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use crate::Chunk;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    proptest! {
+        /**
+         * Frames are delivered 0 to 2 times each, in an arbitrary order:
+         * the data is reassembled exactly when nothing was dropped,
+         * otherwise `missing()` names exactly the dropped chunks it can know of
+         */
+        #[test]
+        fn test_reassemble_any_order(
+            data in vec(any::<u8>(), 0..1500),
+            max_chunk_size in 20usize..300,
+            plan in vec((0usize..3, any::<u32>()), 160),
+        ) {
+            let chunk = Chunk::new(max_chunk_size, 0x10, &data).with_crc().with_last_flag();
+            let frames = chunk.to_frames();
+            prop_assert!(!frames.is_empty() && frames.len() <= plan.len());
+            let mut deliveries: Vec<(u32, usize)> = Vec::new();
+            for (counter, &(copies, key)) in plan.iter().take(frames.len()).enumerate() {
+                deliveries.extend(core::iter::repeat_n((key, counter), copies));
+            }
+            deliveries.sort();
+
+            let mut dechunker = Dechunker::new().with_crc().with_last_flag();
+            let mut result = None;
+            for &(_, counter) in &deliveries {
+                if let Some(reassembled) = dechunker.push(&frames[counter]).unwrap() {
+                    prop_assert!(result.is_none());
+                    result = Some(reassembled);
+                }
+            }
+
+            let received = |counter: &usize| deliveries.iter().any(|&(_, c)| c == *counter);
+            let last = frames.len() - 1;
+            let end = if received(&last) {
+                last + 1
+            } else {
+                deliveries.iter().map(|&(_, c)| c + 1).max().unwrap_or(0)
+            };
+            let dropped: Vec<usize> = (0..end).filter(|counter| !received(counter)).collect();
+            if (0..frames.len()).all(|counter| received(&counter)) {
+                prop_assert_eq!(result, Some(data));
+                prop_assert!(dechunker.is_complete());
+            } else {
+                prop_assert!(result.is_none());
+                prop_assert_eq!(dechunker.missing(), dropped);
+            }
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use crate::crc::{crc32, Crc16};
+use crate::format::FrameFormat;
+use crate::{Checksum, ChunkError, CounterWidth, Endianness, HeaderFlags, LengthWidth, MetaWidth};
+
+/**
+ * `Dechunker` reassembling into a caller buffer, no allocation involved.
+ * Payloads are written at their offset as they arrive, so chunks are expected
+ * in order, repeated chunks are ignored. Compressed messages aren't supported.
+ */
+pub struct DechunkerRef<'b> {
+    buf: &'b mut [u8],
+    format: FrameFormat,
+    topic: Option<u8>,
+    len: Option<usize>,
+    received: usize,
+    next: usize,
+    complete: bool,
+    session_id: Option<u16>,
+    expected_crc: Option<u32>,
+    flags: Option<HeaderFlags>,
+    total: Option<usize>,
+}
+
+impl<'b> DechunkerRef<'b> {
+    pub fn new_in(buf: &'b mut [u8]) -> Self {
+        DechunkerRef {
+            buf,
+            format: FrameFormat::default(),
+            topic: None,
+            len: None,
+            received: 0,
+            next: 0,
+            complete: false,
+            session_id: None,
+            expected_crc: None,
+            flags: None,
+            total: None,
+        }
+    }
+
+    /**
+     * See `Dechunker::with_crc`
+     */
+    pub fn with_crc(self) -> Self {
+        self.with_checksum(&Crc16)
+    }
+
+    /**
+     * See `Dechunker::with_checksum`
+     */
+    pub fn with_checksum(mut self, checksum: &'static dyn Checksum) -> Self {
+        self.format.checksum = Some(checksum);
+        self
+    }
+
+    pub fn with_meta_width(self, meta_width: MetaWidth) -> Self {
+        self.with_length_width(meta_width)
+            .with_counter_width(meta_width)
+    }
+
+    pub fn with_length_width(mut self, length_width: LengthWidth) -> Self {
+        self.format.length_width = length_width;
+        self
+    }
+
+    pub fn with_counter_width(mut self, counter_width: CounterWidth) -> Self {
+        self.format.counter_width = counter_width;
+        self
+    }
+
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.format.endianness = endianness;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_version`
+     */
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.format.version = Some(version);
+        self
+    }
+
+    /**
+     * See `Dechunker::with_last_flag`
+     */
+    pub fn with_last_flag(mut self) -> Self {
+        self.format.last_flag = true;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_session_ids`
+     */
+    pub fn with_session_ids(mut self) -> Self {
+        self.format.session_ids = true;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_message_crc`
+     */
+    pub fn with_message_crc(mut self) -> Self {
+        self.format.message_crc = true;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_chunk_length`
+     */
+    pub fn with_chunk_length(mut self) -> Self {
+        self.format.chunk_length = true;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_header_flags`
+     */
+    pub fn with_header_flags(mut self) -> Self {
+        self.format.header_flags = true;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_chunk_count`
+     */
+    pub fn with_chunk_count(mut self) -> Self {
+        self.format.chunk_count = true;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_repeat_header`
+     */
+    pub fn with_repeat_header(mut self) -> Self {
+        self.format.repeat_header = true;
+        self
+    }
+
+    /**
+     * See `Dechunker::with_alignment`
+     */
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.format.alignment = Some(alignment);
+        self
+    }
+
+    /**
+     * See `Dechunker::allowed_topics`
+     */
+    pub fn allowed_topics(&mut self, topics: &[u8]) {
+        self.format.allow_topics(topics);
+    }
+
+    /**
+     * See `Dechunker::with_varint`
+     */
+    pub fn with_varint(mut self) -> Self {
+        self.format.varint = true;
+        self
+    }
+
+    pub fn topic(&self) -> Option<u8> {
+        self.topic
+    }
+
+    /**
+     * See `Dechunker::flags`
+     */
+    pub fn flags(&self) -> Option<HeaderFlags> {
+        self.flags
+    }
+
+    pub fn session_id(&self) -> Option<u16> {
+        self.session_id
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /**
+     * Bytes reassembled so far
+     */
+    pub fn data(&self) -> &[u8] {
+        &self.buf[..self.received]
+    }
+
+    /**
+     * Accepts the next wire frame, returns the data length once the length
+     * declared in the header is reached, the data is then `data()`.
+     * Fails with `BufferTooSmall` when the declared length exceeds the buffer
+     * and with `UnexpectedChunkNumber` for a chunk out of order.
+     * An abort frame of `Chunk::encode_abort` fails with `Aborted`
+     * and the message starts over
+     */
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<usize>, ChunkError> {
+        let decoded = self.format.decode(frame)?;
+        match (self.session_id, decoded.session_id) {
+            (Some(expected), Some(got)) if expected != got => {
+                return Err(ChunkError::SessionMismatch { expected, got });
+            }
+            (None, Some(_)) => self.session_id = decoded.session_id,
+            _ => {}
+        }
+        if decoded.aborted {
+            self.topic = None;
+            self.len = None;
+            self.received = 0;
+            self.next = 0;
+            self.complete = false;
+            self.session_id = None;
+            self.expected_crc = None;
+            self.flags = None;
+            self.total = None;
+            return Err(ChunkError::Aborted);
+        }
+        // retransmitted chunks that did arrive before are ignored
+        if self.complete || decoded.counter < self.next {
+            return Ok(None);
+        }
+        if decoded.counter != self.next {
+            return Err(ChunkError::UnexpectedChunkNumber {
+                expected: Some(self.next),
+                got: decoded.counter,
+            });
+        }
+        let total = decoded
+            .header
+            .as_ref()
+            .map_or(self.total, |header| header.chunk_count);
+        if let Some(total) = total.filter(|&total| decoded.counter >= total) {
+            return Err(ChunkError::ChunkCountMismatch {
+                total,
+                got: decoded.counter,
+            });
+        }
+        if let Some(header) = decoded.header {
+            if header.len > self.buf.len() {
+                return Err(ChunkError::BufferTooSmall {
+                    expected: header.len,
+                    got: self.buf.len(),
+                });
+            }
+            self.topic = Some(header.topic);
+            self.len = Some(header.len);
+            self.expected_crc = header.message_crc;
+            self.flags = header.flags;
+            self.total = header.chunk_count;
+        }
+        let len = self.len.unwrap_or(0);
+        let end = self.received + decoded.payload.len();
+        if end > len || (decoded.is_last && end < len) {
+            return Err(ChunkError::LengthMismatch {
+                expected: len,
+                got: end,
+            });
+        }
+        self.buf[self.received..end].copy_from_slice(decoded.payload);
+        self.received = end;
+        self.next += 1;
+        if end < len {
+            return Ok(None);
+        }
+        self.complete = true;
+        if let Some(expected) = self.expected_crc {
+            if crc32(self.data()) != expected {
+                return Err(ChunkError::MessageCrcMismatch);
+            }
+        }
+        Ok(Some(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chunk;
+
+    fn push_all(dechunker: &mut DechunkerRef, chunk: &Chunk) -> Option<usize> {
+        let mut result = None;
+        let mut frame = [0; 250];
+        for counter in 0..chunk.total_chunks() {
+            let len = chunk.encode_into(counter, &mut frame).unwrap();
+            result = dechunker.push(&frame[..len]).unwrap();
+        }
+        result
+    }
+
+    #[test]
+    fn test_reassemble_in() {
+        let mut data = [0; 1000];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let chunk = Chunk::new(250, 0x10, &data).with_crc().with_message_crc();
+        let mut buf = [0; 1024];
+        let mut dechunker = DechunkerRef::new_in(&mut buf).with_crc().with_message_crc();
+        assert_eq!(push_all(&mut dechunker, &chunk), Some(1000));
+        assert!(dechunker.is_complete());
+        assert_eq!(dechunker.topic(), Some(0x10));
+        assert_eq!(dechunker.data(), data);
+
+        let mut frame = [0; 250];
+        let len = chunk.encode_into(2, &mut frame).unwrap();
+        assert_eq!(dechunker.push(&frame[..len]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_reassemble_in_order() {
+        let data = [7; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let mut buf = [0; 1000];
+        let mut dechunker = DechunkerRef::new_in(&mut buf);
+        let mut frame = [0; 250];
+        let len = chunk.encode_into(0, &mut frame).unwrap();
+        assert_eq!(dechunker.push(&frame[..len]).unwrap(), None);
+        assert_eq!(dechunker.push(&frame[..len]).unwrap(), None);
+        let len = chunk.encode_into(2, &mut frame).unwrap();
+        assert!(matches!(
+            dechunker.push(&frame[..len]),
+            Err(ChunkError::UnexpectedChunkNumber {
+                expected: Some(1),
+                got: 2
+            })
+        ));
+        assert_eq!(dechunker.data().len(), chunk.first_chunk_capacity());
+    }
+
+    #[test]
+    fn test_reassemble_in_varint() {
+        let mut data = [0; 1000];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let chunk = Chunk::new(8, 0x10, &data).with_varint().with_last_flag();
+        let mut buf = [0; 1000];
+        let mut dechunker = DechunkerRef::new_in(&mut buf)
+            .with_varint()
+            .with_last_flag();
+        assert_eq!(push_all(&mut dechunker, &chunk), Some(1000));
+        assert_eq!(dechunker.data(), data);
+    }
+
+    #[test]
+    fn test_reassemble_in_chunk_count() {
+        let data = [7; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_chunk_count()
+            .with_varint();
+        let mut buf = [0; 1000];
+        let mut dechunker = DechunkerRef::new_in(&mut buf)
+            .with_chunk_count()
+            .with_varint();
+        assert_eq!(push_all(&mut dechunker, &chunk), Some(1000));
+
+        let mut buf = [0; 1000];
+        let mut dechunker = DechunkerRef::new_in(&mut buf)
+            .with_chunk_count()
+            .with_varint();
+        let mut frame = [0; 250];
+        let len = chunk.encode_into(0, &mut frame).unwrap();
+        assert_eq!(frame[..5], [0, 0x10, 0xE8, 0x07, 4]);
+        frame[4] = 2;
+        dechunker.push(&frame[..len]).unwrap();
+        for counter in 1..3 {
+            let len = chunk.encode_into(counter, &mut frame).unwrap();
+            dechunker.push(&frame[..len]).unwrap();
+        }
+        let len = chunk.encode_into(3, &mut frame).unwrap();
+        assert!(matches!(
+            dechunker.push(&frame[..len]),
+            Err(ChunkError::ChunkCountMismatch { total: 3, got: 3 })
+        ));
+    }
+
+    #[test]
+    fn test_buffer_too_small() {
+        let data = [7; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let mut buf = [0; 999];
+        let mut dechunker = DechunkerRef::new_in(&mut buf);
+        let mut frame = [0; 250];
+        let len = chunk.encode_into(0, &mut frame).unwrap();
+        assert!(matches!(
+            dechunker.push(&frame[..len]),
+            Err(ChunkError::BufferTooSmall {
+                expected: 1000,
+                got: 999
+            })
+        ));
+        assert!(dechunker.data().is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::vec;
+use std::vec::Vec;
+
+use crate::{Checksum, Chunk, ChunkError, Endianness, MetaWidth};
+
+/**
+ * Frames a file without reading it into memory: the length in the header is
+ * the file size from the current position on, payloads are read
+ * `chunk_capacity()` bytes at a time as frames are pulled.
+ * Frames are laid out by `Chunk`, the same as `Chunk::encode_into` gives for
+ * the whole content with the same options,
+ * any `Read + Seek` source works, e.g. an `io::Cursor`.
+ * A failed read ends the iteration after its error.
+ */
+pub struct FileChunker<R = File> {
+    file: R,
+    chunk: Chunk<'static>,
+    failed: bool,
+}
+
+impl<R: Read + Seek> FileChunker<R> {
+    /**
+     * Measures the remaining length of file by seeking to its end and back
+     */
+    pub fn new(mut file: R, max_chunk_size: usize, topic: impl Into<u8>) -> io::Result<Self> {
+        let start = file.stream_position()?;
+        let end = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(start))?;
+        let len = usize::try_from(end.saturating_sub(start))
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large"))?;
+        Ok(FileChunker {
+            file,
+            chunk: Chunk::gathered(max_chunk_size, topic.into(), len),
+            failed: false,
+        })
+    }
+
+    /**
+     * See `Chunk::with_crc`
+     */
+    pub fn with_crc(mut self) -> Self {
+        self.chunk = self.chunk.with_crc();
+        self
+    }
+
+    /**
+     * See `Chunk::with_checksum`
+     */
+    pub fn with_checksum(mut self, checksum: &'static dyn Checksum) -> Self {
+        self.chunk = self.chunk.with_checksum(checksum);
+        self
+    }
+
+    /**
+     * See `Chunk::with_meta_width`
+     */
+    pub fn with_meta_width(mut self, meta_width: MetaWidth) -> Self {
+        self.chunk = self.chunk.with_meta_width(meta_width);
+        self
+    }
+
+    /**
+     * See `Chunk::with_endianness`
+     */
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.chunk = self.chunk.with_endianness(endianness);
+        self
+    }
+
+    /**
+     * Bytes of the file going into the header
+     */
+    pub fn len(&self) -> usize {
+        self.chunk.data_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn counter(&self) -> usize {
+        self.chunk.counter()
+    }
+
+    /**
+     * Payload bytes of every chunk but the first, which also carries the header
+     */
+    pub fn chunk_capacity(&self) -> usize {
+        self.chunk.chunk_capacity()
+    }
+
+    fn read_frame(&mut self) -> Result<Vec<u8>, ChunkError> {
+        let mut frame = vec![0; self.chunk.max_chunk_size()];
+        let file = &mut self.file;
+        let len =
+            self.chunk
+                .encode_frame_with(self.chunk.counter, true, &mut frame, |_, payload| {
+                    // read_exact retries short reads and `Interrupted`
+                    Ok(file.read_exact(payload)?)
+                })?;
+        frame.truncate(len);
+        self.chunk.counter += 1;
+        Ok(frame)
+    }
+}
+
+impl<R: Read + Seek> Iterator for FileChunker<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    /**
+     * Next wire frame, framing errors are reported as `io::ErrorKind::InvalidInput`,
+     * a file shrunk meanwhile as `io::ErrorKind::UnexpectedEof`
+     */
+    fn next(&mut self) -> Option<Self::Item> {
+        let counter = self.chunk.counter;
+        if self.failed || (counter > 0 && counter >= self.chunk.total_chunks()) {
+            return None;
+        }
+        let frame = self.read_frame().map_err(|err| match err {
+            ChunkError::Io(err) => err,
+            ChunkError::UnexpectedEof => io::ErrorKind::UnexpectedEof.into(),
+            err => io::Error::new(io::ErrorKind::InvalidInput, err),
+        });
+        self.failed = frame.is_err();
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Crc32, Dechunker};
+    use std::format;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn test_file_chunker() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let path = std::env::temp_dir().join(format!("chanker-{}.bin", std::process::id()));
+        File::create(&path).unwrap().write_all(&data).unwrap();
+        let chunker = FileChunker::new(File::open(&path).unwrap(), 250, 0x10)
+            .unwrap()
+            .with_crc();
+        assert_eq!(chunker.len(), 1000);
+        let frames: Vec<Vec<u8>> = chunker.map(Result::unwrap).collect();
+        std::fs::remove_file(&path).unwrap();
+
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        assert_eq!(frames, chunk.to_frames());
+        let mut dechunker = Dechunker::new().with_crc();
+        let mut result = None;
+        for frame in &frames {
+            result = dechunker.push(frame).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_file_chunker_from_position() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut cursor = Cursor::new(&data);
+        cursor.set_position(400);
+        let chunker = FileChunker::new(cursor, 250, 0x10)
+            .unwrap()
+            .with_meta_width(MetaWidth::U16);
+        assert_eq!(chunker.len(), 600);
+        assert_eq!(chunker.chunk_capacity(), 248);
+        let frames: Vec<Vec<u8>> = chunker.map(Result::unwrap).collect();
+        let chunk = Chunk::new(250, 0x10, &data[400..]).with_meta_width(MetaWidth::U16);
+        assert_eq!(frames, chunk.to_frames());
+
+        let chunker = FileChunker::new(Cursor::new(&data), 250, 0x10)
+            .unwrap()
+            .with_checksum(&Crc32)
+            .with_endianness(Endianness::Big);
+        let frames: Vec<Vec<u8>> = chunker.map(Result::unwrap).collect();
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_checksum(&Crc32)
+            .with_endianness(Endianness::Big);
+        assert_eq!(frames, chunk.to_frames());
+
+        let mut chunker = FileChunker::new(Cursor::new(&data), 10, 0x10).unwrap();
+        let err = chunker.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(chunker.next().is_none());
+    }
+}
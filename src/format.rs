@@ -0,0 +1,458 @@
+use crate::crc::{checksum_width, Crc16, MESSAGE_CRC_SIZE};
+use crate::meta;
+use crate::{
+    verify_checksum, Checksum, ChunkError, CounterWidth, Endianness, Header, HeaderFlags,
+    LengthWidth, MetaWidth, FLAG_CHUNK_COUNT, FLAG_CHUNK_LENGTH, FLAG_COMPRESSED, FLAG_CRC,
+    FLAG_HAS_SESSION_ID, FLAG_LAST_CHUNK, FLAG_MESSAGE_CRC, PADDING_LEN_SIZE, SESSION_ID_SIZE,
+};
+
+/**
+ * Wire options a receiver expects, the mirror of `Chunk` options
+ */
+#[derive(Default, Debug, Clone, Copy)]
+pub(crate) struct FrameFormat {
+    pub(crate) checksum: Option<&'static dyn Checksum>,
+    pub(crate) length_width: LengthWidth,
+    pub(crate) counter_width: CounterWidth,
+    pub(crate) endianness: Endianness,
+    pub(crate) version: Option<u8>,
+    pub(crate) compression: bool,
+    pub(crate) last_flag: bool,
+    pub(crate) session_ids: bool,
+    pub(crate) message_crc: bool,
+    pub(crate) chunk_length: bool,
+    pub(crate) header_flags: bool,
+    pub(crate) varint: bool,
+    pub(crate) chunk_count: bool,
+    pub(crate) repeat_header: bool,
+    pub(crate) terminator: bool,
+    pub(crate) alignment: Option<usize>,
+    /**
+     * Chunk 0 comes without header, it is delivered by `decode_header`
+     */
+    pub(crate) detached_header: bool,
+    /**
+     * Bit per topic accepted in a header, any topic when None
+     */
+    pub(crate) allowed_topics: Option<[u8; 32]>,
+}
+
+/**
+ * Header of the first chunk
+ */
+pub(crate) struct FirstHeader {
+    pub(crate) topic: u8,
+    /**
+     * Data length without the compressed flag
+     */
+    pub(crate) len: usize,
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    pub(crate) compressed: bool,
+    pub(crate) message_crc: Option<u32>,
+    pub(crate) flags: Option<HeaderFlags>,
+    #[cfg_attr(not(feature = "alloc"), allow(dead_code))]
+    pub(crate) chunk_count: Option<usize>,
+    /**
+     * Bytes of the header in the frame
+     */
+    pub(crate) header_len: usize,
+}
+
+pub(crate) struct DecodedFrame<'f> {
+    pub(crate) counter: usize,
+    pub(crate) is_last: bool,
+    pub(crate) session_id: Option<u16>,
+    pub(crate) header: Option<FirstHeader>,
+    /**
+     * Bytes of counter, chunk length and session id
+     */
+    pub(crate) prefix_len: usize,
+    pub(crate) payload: &'f [u8],
+    /**
+     * Abort frame of `Chunk::encode_abort`, told by its reserved counter
+     */
+    pub(crate) aborted: bool,
+}
+
+impl FrameFormat {
+    /**
+     * Format of `Chunk::with_header_flags` frames sent with flags,
+     * default widths and CRC-16 for `FLAG_CRC`
+     */
+    pub(crate) fn from_flags(flags: HeaderFlags) -> Self {
+        FrameFormat {
+            checksum: match flags.contains(FLAG_CRC) {
+                true => Some(&Crc16),
+                false => None,
+            },
+            compression: flags.contains(FLAG_COMPRESSED),
+            last_flag: flags.contains(FLAG_LAST_CHUNK),
+            session_ids: flags.contains(FLAG_HAS_SESSION_ID),
+            message_crc: flags.contains(FLAG_MESSAGE_CRC),
+            chunk_length: flags.contains(FLAG_CHUNK_LENGTH),
+            header_flags: true,
+            chunk_count: flags.contains(FLAG_CHUNK_COUNT),
+            ..Default::default()
+        }
+    }
+
+    /**
+     * Offset of the first plausible chunk 0 in buf, see `crate::find_header`
+     */
+    pub(crate) fn find_header(&self, buf: &[u8], max_len: Option<usize>) -> Option<usize> {
+        (0..buf.len()).find(|&start| self.first_frame(&buf[start..], max_len).is_some())
+    }
+
+    /**
+     * Length of the first frame of a message bytes start with, only when it
+     * is fully checked: with `chunk_length` its end is known, and its
+     * checksum matches. None for anything else, plausible headers included
+     */
+    #[cfg(feature = "alloc")]
+    pub(crate) fn checked_first_frame(
+        &self,
+        bytes: &[u8],
+        max_len: Option<usize>,
+    ) -> Option<usize> {
+        self.first_frame(bytes, max_len).flatten()
+    }
+
+    /**
+     * Some when bytes start with counter 0 and a header this format reads,
+     * declaring at most max_len bytes and no more chunks than bytes.
+     * With `chunk_length` the frame end is known, its payload has to fit
+     * the declared length, with a checksum too that has to match and
+     * the frame length is returned
+     */
+    fn first_frame(&self, bytes: &[u8], max_len: Option<usize>) -> Option<Option<usize>> {
+        let Ok((0, _, mut pos)) = self.read_meta(bytes, self.counter_width, self.flagged_counter())
+        else {
+            return None;
+        };
+        let mut chunk_length = None;
+        if self.chunk_length {
+            let Ok((len, _, size)) = self.read_meta(&bytes[pos..], self.counter_width, false)
+            else {
+                return None;
+            };
+            chunk_length = Some(len);
+            pos += size;
+        }
+        if self.session_ids {
+            pos += SESSION_ID_SIZE;
+        }
+        let mut padding = 0;
+        if let Some(alignment) = self.alignment {
+            match bytes.get(pos) {
+                Some(&len) if (len as usize) < alignment => padding = len as usize,
+                _ => return None,
+            }
+            pos += PADDING_LEN_SIZE;
+        }
+        let Some(Ok(header)) = bytes.get(pos..).map(|rest| self.read_header(rest)) else {
+            return None;
+        };
+        if max_len.is_some_and(|max| header.len > max) {
+            return None;
+        }
+        // chunks after the first carry at least a byte, as in `Dechunker`
+        let data_chunks = header
+            .chunk_count
+            .map(|total| total.saturating_sub(self.terminator as usize));
+        if data_chunks.is_some_and(|chunks| chunks.saturating_sub(1) > header.len) {
+            return None;
+        }
+        let Some(chunk_length) = chunk_length else {
+            return Some(None);
+        };
+        if chunk_length > header.len {
+            return None;
+        }
+        let Some(checksum) = self.checksum else {
+            return Some(None);
+        };
+        let end = pos + header.header_len + chunk_length + padding + checksum_width(checksum);
+        let frame = bytes.get(..end)?;
+        verify_checksum(frame, checksum).ok()?;
+        Some(Some(end))
+    }
+
+    /**
+     * Verifies and splits a single wire frame
+     */
+    pub(crate) fn decode<'f>(&self, frame: &'f [u8]) -> Result<DecodedFrame<'f>, ChunkError> {
+        if let Some(alignment) = self.alignment.filter(|&alignment| alignment > 1) {
+            if !frame.len().is_multiple_of(alignment) {
+                return Err(ChunkError::LengthMismatch {
+                    expected: frame.len().next_multiple_of(alignment),
+                    got: frame.len(),
+                });
+            }
+        }
+        let frame = match self.checksum {
+            Some(checksum) => verify_checksum(frame, checksum)?,
+            None => frame,
+        };
+        let (counter, is_last, mut pos) =
+            self.read_meta(frame, self.counter_width, self.flagged_counter())?;
+        let mut chunk_length = None;
+        if self.chunk_length {
+            let (len, _, size) = self.read_meta(&frame[pos..], self.counter_width, false)?;
+            chunk_length = Some(len);
+            pos += size;
+        }
+        let mut session_id = None;
+        if self.session_ids {
+            session_id = Some(self.read_session_id(frame, pos)?);
+            pos += SESSION_ID_SIZE;
+        }
+        let mut end = frame.len();
+        if self.alignment.is_some() {
+            let padding = *frame.get(pos).ok_or(ChunkError::InvalidMetaSize {
+                expected: pos + PADDING_LEN_SIZE,
+                got: frame.len(),
+            })? as usize;
+            pos += PADDING_LEN_SIZE;
+            if padding > end - pos {
+                return Err(ChunkError::InvalidMetaSize {
+                    expected: pos + padding,
+                    got: frame.len(),
+                });
+            }
+            end -= padding;
+        }
+        let mut payload = &frame[pos..end];
+        let mut header = None;
+        let aborted =
+            counter == meta::abort_counter(self.counter_width, self.flagged_counter(), self.varint);
+        if ((counter == 0 && !self.detached_header) || self.repeat_header) && !aborted {
+            let first = self.read_header(payload)?;
+            payload = &payload[first.header_len..];
+            header = Some(first);
+        }
+        check_chunk_length(chunk_length, payload)?;
+        Ok(DecodedFrame {
+            counter,
+            is_last,
+            session_id,
+            header,
+            prefix_len: pos,
+            payload,
+            aborted,
+        })
+    }
+
+    /**
+     * Top bit of the counter marks the final chunk or the terminator,
+     * see `Chunk::with_terminator`
+     */
+    fn flagged_counter(&self) -> bool {
+        self.last_flag || self.terminator
+    }
+
+    /**
+     * Accepts headers of the given topics only
+     */
+    pub(crate) fn allow_topics(&mut self, topics: &[u8]) {
+        let mut allowed = [0; 32];
+        for &topic in topics {
+            allowed[topic as usize / 8] |= 1 << (topic % 8);
+        }
+        self.allowed_topics = Some(allowed);
+    }
+
+    /**
+     * Verifies and reads a header framed on its own, see `Chunk::header_frame`
+     */
+    #[cfg(feature = "alloc")]
+    pub(crate) fn decode_header(&self, frame: &[u8]) -> Result<FirstHeader, ChunkError> {
+        let bytes = match self.checksum {
+            Some(checksum) => verify_checksum(frame, checksum)?,
+            None => frame,
+        };
+        let header = self.read_header(bytes)?;
+        if header.header_len != bytes.len() {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: header.header_len,
+                got: bytes.len(),
+            });
+        }
+        Ok(header)
+    }
+
+    /**
+     * Reads a counter or length field, width or varint wide,
+     * returns value, its top-bit flag when flagged is set and size in bytes
+     */
+    fn read_meta(
+        &self,
+        bytes: &[u8],
+        width: MetaWidth,
+        flagged: bool,
+    ) -> Result<(usize, bool, usize), ChunkError> {
+        if self.varint {
+            return meta::read_varint_flagged(bytes, flagged);
+        }
+        let (value, flag) = if flagged {
+            width.read_flagged(bytes, self.endianness)?
+        } else {
+            (width.read(bytes, self.endianness)?, false)
+        };
+        Ok((value, flag, width.size()))
+    }
+
+    /**
+     * Bytes of the header, the shortest varint length with `varint` set
+     */
+    pub(crate) fn header_len(&self) -> usize {
+        let (len_size, count_size) = if self.varint {
+            (1, 1)
+        } else {
+            (self.length_width.size(), self.counter_width.size())
+        };
+        let message_crc_size = if self.message_crc {
+            MESSAGE_CRC_SIZE
+        } else {
+            0
+        };
+        let chunk_count_size = if self.chunk_count { count_size } else { 0 };
+        self.version.is_some() as usize
+            + 1
+            + self.header_flags as usize
+            + len_size
+            + chunk_count_size
+            + message_crc_size
+    }
+
+    fn read_session_id(&self, frame: &[u8], pos: usize) -> Result<u16, ChunkError> {
+        let bytes = frame
+            .get(pos..pos + SESSION_ID_SIZE)
+            .ok_or(ChunkError::InvalidMetaSize {
+                expected: pos + SESSION_ID_SIZE,
+                got: frame.len(),
+            })?;
+        let bytes = [bytes[0], bytes[1]];
+        Ok(match self.endianness {
+            Endianness::Little => u16::from_le_bytes(bytes),
+            Endianness::Big => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn read_header(&self, payload: &[u8]) -> Result<FirstHeader, ChunkError> {
+        let bytes = match self.version {
+            Some(version) => Header::strip_version(payload, version, self.header_len())?,
+            None => payload,
+        };
+        let (topic, flags, len_bytes) = if self.varint {
+            // topic and flags byte as in `Header::parse_flagged`, varint length after them
+            let len_pos = 1 + self.header_flags as usize;
+            if bytes.len() < len_pos {
+                return Err(ChunkError::InvalidMetaSize {
+                    expected: self.header_len(),
+                    got: payload.len(),
+                });
+            }
+            let flags = match self.header_flags {
+                true => Some(HeaderFlags::from_bits(bytes[1])?),
+                false => None,
+            };
+            (bytes[0], flags, &bytes[len_pos..])
+        } else if self.header_flags {
+            let (topic, flags, _) =
+                Header::parse_flagged(bytes, self.length_width, self.endianness)?;
+            (topic, Some(flags), &bytes[2..])
+        } else {
+            let (topic, _) = Header::parse(bytes, self.length_width, self.endianness)?;
+            (topic, None, &bytes[1..])
+        };
+        if let Some(allowed) = self.allowed_topics {
+            if allowed[topic as usize / 8] & (1 << (topic % 8)) == 0 {
+                return Err(ChunkError::UnexpectedTopic(topic));
+            }
+        }
+        if let Some(flags) = flags {
+            self.check_flags(flags)?;
+        }
+        // without the flags byte the top bit of the length is the compressed flag
+        let (len, top_bit, len_size) = self.read_meta(
+            len_bytes,
+            self.length_width,
+            self.compression && flags.is_none(),
+        )?;
+        let compressed = match flags {
+            Some(flags) => flags.contains(FLAG_COMPRESSED),
+            None => top_bit,
+        };
+        let mut header_len = payload.len() - len_bytes.len() + len_size;
+        let mut chunk_count = None;
+        if self.chunk_count {
+            let (last, _, size) =
+                self.read_meta(&payload[header_len..], self.counter_width, false)?;
+            chunk_count = Some(last.saturating_add(1));
+            header_len += size;
+        }
+        if self.message_crc {
+            header_len += MESSAGE_CRC_SIZE;
+        }
+        let mut header = FirstHeader {
+            topic,
+            len,
+            compressed,
+            message_crc: None,
+            flags,
+            chunk_count,
+            header_len,
+        };
+        if self.message_crc {
+            let crc = payload
+                .get(header_len - MESSAGE_CRC_SIZE..header_len)
+                .ok_or(ChunkError::InvalidMetaSize {
+                    expected: header_len,
+                    got: payload.len(),
+                })?;
+            header.message_crc = Some(u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]));
+        }
+        Ok(header)
+    }
+
+    fn check_flags(&self, flags: HeaderFlags) -> Result<(), ChunkError> {
+        let mut expected = HeaderFlags::default();
+        expected.set(FLAG_CRC, self.checksum.is_some());
+        expected.set(FLAG_HAS_SESSION_ID, self.session_ids);
+        expected.set(FLAG_LAST_CHUNK, self.last_flag);
+        expected.set(FLAG_MESSAGE_CRC, self.message_crc);
+        expected.set(FLAG_CHUNK_LENGTH, self.chunk_length);
+        expected.set(FLAG_CHUNK_COUNT, self.chunk_count);
+        // compression is up to the sender, as long as the receiver can inflate
+        expected.set(
+            FLAG_COMPRESSED,
+            self.compression && flags.contains(FLAG_COMPRESSED),
+        );
+        if flags != expected {
+            return Err(ChunkError::FlagsMismatch {
+                expected: expected.bits(),
+                got: flags.bits(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn check_chunk_length(expected: Option<usize>, payload: &[u8]) -> Result<(), ChunkError> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    if payload.len() < expected {
+        return Err(ChunkError::TruncatedChunk {
+            expected,
+            got: payload.len(),
+        });
+    }
+    if payload.len() > expected {
+        return Err(ChunkError::LengthMismatch {
+            expected,
+            got: payload.len(),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use core::ops::Range;
+
+use crate::format::FrameFormat;
+use crate::ChunkError;
+
+/**
+ * Single chunk of a message with the data needed to put it on the wire,
+ * see `Chunk::encode_into`
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub topic: u8,
+    pub counter: usize,
+    pub payload: &'a [u8],
+    /**
+     * Index of the first payload byte within the whole data
+     */
+    pub data_offset: usize,
+    /**
+     * Number of chunks of the message, for frames decoded by
+     * `Dechunker::decode_frame` known with `Dechunker::with_chunk_count`
+     * once the first chunk arrived
+     */
+    pub total_chunks: Option<usize>,
+    /**
+     * Transfer id, see `Chunk::with_session_id`
+     */
+    pub session_id: Option<u16>,
+    /**
+     * Carries the header
+     */
+    pub is_first: bool,
+    /**
+     * Final chunk: payload runs to the end of data, or it is the terminator
+     * of `Chunk::with_terminator`. For frames decoded by
+     * `Dechunker::decode_frame` this is the last-chunk flag of the wire
+     * (see `Chunk::with_last_flag`), without it only a single-chunk message is known
+     */
+    pub is_last: bool,
+}
+
+/**
+ * Decodes a wire frame of `Chunk::new` without options from the bytes and
+ * max_chunk_size of the sender: meta counter, the header when the counter
+ * is 0, payload. The chunk size hint places the payload of any chunk, the
+ * short final one included, a counter whose offset overflows fails with
+ * `CounterOutOfRange`. Frames after the first don't carry the topic, it is
+ * 0 for them. `Dechunker::decode_frame` handles the other wire options
+ */
+impl<'a> TryFrom<(&'a [u8], usize)> for Frame<'a> {
+    type Error = ChunkError;
+
+    fn try_from((bytes, max_chunk_size): (&'a [u8], usize)) -> Result<Self, Self::Error> {
+        let format = FrameFormat::default();
+        let decoded = format.decode(bytes)?;
+        if decoded.aborted {
+            return Err(ChunkError::Aborted);
+        }
+        let (topic, data_offset, is_last, total_chunks) = match &decoded.header {
+            Some(header) => (
+                header.topic,
+                0,
+                decoded.payload.len() == header.len,
+                header.chunk_count,
+            ),
+            None => {
+                let per_chunk = max_chunk_size
+                    .checked_sub(decoded.prefix_len)
+                    .ok_or(ChunkError::CounterOutOfRange)?;
+                let data_offset = per_chunk
+                    .checked_sub(format.header_len())
+                    .zip((decoded.counter - 1).checked_mul(per_chunk))
+                    .and_then(|(first, offset)| offset.checked_add(first))
+                    .ok_or(ChunkError::CounterOutOfRange)?;
+                (0, data_offset, false, None)
+            }
+        };
+        Ok(Frame {
+            topic,
+            counter: decoded.counter,
+            payload: decoded.payload,
+            data_offset,
+            total_chunks,
+            session_id: decoded.session_id,
+            is_first: decoded.counter == 0,
+            is_last,
+        })
+    }
+}
+
+/**
+ * Byte layout of one wire frame of `Chunk::encode_into`, see `Chunk::describe_layout`.
+ * The frame is meta_bytes, header_bytes, the payload and crc_bytes in that order
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkLayout {
+    /**
+     * Chunk counter
+     */
+    pub index: usize,
+    /**
+     * Bytes of `Chunk::header()`, 0 on every chunk but the first
+     */
+    pub header_bytes: usize,
+    /**
+     * Bytes of counter, chunk length, session id and padding length
+     */
+    pub meta_bytes: usize,
+    /**
+     * Bytes of the data carried as payload
+     */
+    pub payload_range: Range<usize>,
+    /**
+     * Zero bytes after the payload of `Chunk::with_alignment`
+     */
+    pub padding_bytes: usize,
+    /**
+     * Bytes of the trailing checksum
+     */
+    pub crc_bytes: usize,
+    /**
+     * Bytes of the whole frame
+     */
+    pub total: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chunk;
+
+    #[test]
+    fn test_frame_try_from() {
+        let data = [7; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let mut out = [0; 250];
+        for expected in chunk.frames() {
+            let len = chunk.encode_into(expected.counter, &mut out).unwrap();
+            let frame = Frame::try_from((&out[..len], 250)).unwrap();
+            assert_eq!(frame.counter, expected.counter);
+            assert_eq!(frame.payload, expected.payload);
+            assert_eq!(frame.data_offset, expected.data_offset);
+            assert_eq!(frame.is_first, expected.is_first);
+        }
+        let len = chunk.encode_into(0, &mut out).unwrap();
+        assert_eq!(Frame::try_from((&out[..len], 250)).unwrap().topic, 0x10);
+        assert!(matches!(
+            Frame::try_from((&out[..3], 250)),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+        // the final chunk is short, its offset is still 17 + 242 * 4 less 9
+        let last = chunk.frames().last().unwrap();
+        let len = chunk.encode_into(last.counter, &mut out).unwrap();
+        let frame = Frame::try_from((&out[..len], 250)).unwrap();
+        assert_eq!((frame.data_offset, frame.payload.len()), (959, 41));
+
+        let mut huge = [0; 12];
+        huge[..8].copy_from_slice(&(u64::MAX / 4).to_le_bytes());
+        assert!(matches!(
+            Frame::try_from((&huge[..], 250)),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+    }
+}
@@ -0,0 +1,239 @@
+use core::fmt;
+use core::ops::Deref;
+
+use crate::meta::{self, MAX_VARINT_LEN};
+use crate::{ChunkError, Endianness, MetaWidth};
+
+pub(crate) const MAX_HEADER_LEN: usize = 3 + 2 * MAX_VARINT_LEN + crate::crc::MESSAGE_CRC_SIZE;
+
+/** Every frame ends with a CRC-16, `Chunk::with_crc` */
+pub const FLAG_CRC: u8 = 1 << 0;
+/** Data is deflated, `Chunk::new_compressed` */
+pub const FLAG_COMPRESSED: u8 = 1 << 1;
+/** Every frame carries a session id, `Chunk::with_session_id` */
+pub const FLAG_HAS_SESSION_ID: u8 = 1 << 2;
+/** The top bit of the counter marks the final chunk, `Chunk::with_last_flag` */
+pub const FLAG_LAST_CHUNK: u8 = 1 << 3;
+/** The header ends with CRC-32 of the whole data, `Chunk::with_message_crc` */
+pub const FLAG_MESSAGE_CRC: u8 = 1 << 4;
+/** Every frame carries its payload length, `Chunk::with_chunk_length` */
+pub const FLAG_CHUNK_LENGTH: u8 = 1 << 5;
+/** The header declares the number of chunks, `Chunk::with_chunk_count` */
+pub const FLAG_CHUNK_COUNT: u8 = 1 << 6;
+
+const FLAGS_MASK: u8 = FLAG_CRC
+    | FLAG_COMPRESSED
+    | FLAG_HAS_SESSION_ID
+    | FLAG_LAST_CHUNK
+    | FLAG_MESSAGE_CRC
+    | FLAG_CHUNK_LENGTH
+    | FLAG_CHUNK_COUNT;
+
+/**
+ * Options the sender framed the message with, the flags byte of a header
+ * written with `Chunk::with_header_flags`. Unused bits are always zero
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderFlags(u8);
+
+impl HeaderFlags {
+    /**
+     * Fails with `UnknownFlags` when any unused bit is set
+     */
+    pub fn from_bits(bits: u8) -> Result<Self, ChunkError> {
+        if bits & !FLAGS_MASK != 0 {
+            return Err(ChunkError::UnknownFlags(bits & !FLAGS_MASK));
+        }
+        Ok(HeaderFlags(bits))
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /**
+     * All bits of flag are set, e.g. `contains(FLAG_CRC)`
+     */
+    pub const fn contains(self, flag: u8) -> bool {
+        self.0 & flag == flag
+    }
+
+    pub(crate) fn set(&mut self, flag: u8, on: bool) {
+        if on {
+            self.0 |= flag;
+        } else {
+            self.0 &= !flag;
+        }
+    }
+}
+
+/**
+ * Bytes of the header of `Chunk::with_header_flags` framed with flags and
+ * meta_width, known before the first frame arrives: topic, flags byte,
+ * length, the last counter with `FLAG_CHUNK_COUNT` and the CRC-32 with
+ * `FLAG_MESSAGE_CRC`. `Chunk::with_version` adds a byte, varint lengths
+ * aren't covered
+ */
+pub const fn header_len(meta_width: MetaWidth, flags: HeaderFlags) -> usize {
+    let chunk_count_size = if flags.contains(FLAG_CHUNK_COUNT) {
+        meta_width.size()
+    } else {
+        0
+    };
+    let message_crc_size = if flags.contains(FLAG_MESSAGE_CRC) {
+        crate::crc::MESSAGE_CRC_SIZE
+    } else {
+        0
+    };
+    2 + meta_width.size() + chunk_count_size + message_crc_size
+}
+
+/**
+ * Resynchronizes a byte stream that lost frame boundaries: offset of the
+ * next plausible first frame of a message in buf, a frame of
+ * `Chunk::with_header_flags` with default widths sent with flags.
+ * A candidate starts with counter 0 and has the version byte, if any, and the
+ * flags byte equal to flags, with `FLAG_CHUNK_COUNT` there are no more
+ * chunks declared than bytes.
+ * With `FLAG_CHUNK_LENGTH` the payload has to fit the declared length and,
+ * with `FLAG_CRC`, the CRC-16 of the frame to match, the strongest check.
+ * A match may still be data that happens to look like a header
+ */
+pub fn find_header(buf: &[u8], flags: HeaderFlags) -> Option<usize> {
+    crate::format::FrameFormat::from_flags(flags).find_header(buf, None)
+}
+
+/**
+ * Encoded header of the first chunk: [topic: u8][len: meta width or varint],
+ * prefixed with [version: u8] when `Chunk::with_version` is set,
+ * with [flags: u8] after the topic when `Chunk::with_header_flags` is set,
+ * with [last counter: meta width or varint] after the length when
+ * `Chunk::with_chunk_count` is set
+ * and followed by [message crc: u32] when `Chunk::with_message_crc` is set
+ */
+#[derive(Clone, Copy)]
+pub struct Header {
+    bytes: [u8; MAX_HEADER_LEN],
+    len: usize,
+}
+
+impl Header {
+    pub(crate) fn new(bytes: [u8; MAX_HEADER_LEN], len: usize) -> Self {
+        Header { bytes, len }
+    }
+
+    /**
+     * Inverse of `Chunk::header()`, returns topic and data length
+     */
+    pub fn parse(
+        bytes: &[u8],
+        meta_width: MetaWidth,
+        endianness: Endianness,
+    ) -> Result<(u8, usize), ChunkError> {
+        if bytes.len() < 1 + meta_width.size() {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: 1 + meta_width.size(),
+                got: bytes.len(),
+            });
+        }
+        let len = meta_width.read(&bytes[1..], endianness)?;
+        Ok((bytes[0], len))
+    }
+
+    /**
+     * Same as `parse` for a header with the flags byte after the topic,
+     * fails with `UnknownFlags` when unused bits are set
+     */
+    pub fn parse_flagged(
+        bytes: &[u8],
+        meta_width: MetaWidth,
+        endianness: Endianness,
+    ) -> Result<(u8, HeaderFlags, usize), ChunkError> {
+        if bytes.len() < 2 + meta_width.size() {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: 2 + meta_width.size(),
+                got: bytes.len(),
+            });
+        }
+        let flags = HeaderFlags::from_bits(bytes[1])?;
+        let len = meta_width.read(&bytes[2..], endianness)?;
+        Ok((bytes[0], flags, len))
+    }
+
+    /**
+     * Same as `parse` for a header written with `Chunk::with_varint`
+     */
+    pub fn parse_varint(bytes: &[u8]) -> Result<(u8, usize), ChunkError> {
+        let topic = *bytes.first().ok_or(ChunkError::InvalidMetaSize {
+            expected: 2,
+            got: 0,
+        })?;
+        let (len, _, _) = meta::read_varint_flagged(&bytes[1..], false)?;
+        Ok((topic, len))
+    }
+
+    /**
+     * Same as `parse` for a header prefixed with the version byte,
+     * fails with `UnsupportedVersion` when it isn't the expected one
+     */
+    pub fn parse_versioned(
+        bytes: &[u8],
+        version: u8,
+        meta_width: MetaWidth,
+        endianness: Endianness,
+    ) -> Result<(u8, usize), ChunkError> {
+        let bytes = Header::strip_version(bytes, version, 2 + meta_width.size())?;
+        Header::parse(bytes, meta_width, endianness)
+    }
+
+    /**
+     * Header without the leading version byte, min_len is the length
+     * expected of the whole header
+     */
+    pub(crate) fn strip_version(
+        bytes: &[u8],
+        version: u8,
+        min_len: usize,
+    ) -> Result<&[u8], ChunkError> {
+        match bytes.first() {
+            None => Err(ChunkError::InvalidMetaSize {
+                expected: min_len,
+                got: 0,
+            }),
+            Some(&got) if got != version => Err(ChunkError::UnsupportedVersion(got)),
+            Some(_) => Ok(&bytes[1..]),
+        }
+    }
+}
+
+impl Deref for Header {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl AsRef<[u8]> for Header {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl PartialEq for Header {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<const N: usize> PartialEq<[u8; N]> for Header {
+    fn eq(&self, other: &[u8; N]) -> bool {
+        **self == other[..]
+    }
+}
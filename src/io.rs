@@ -0,0 +1,190 @@
+use std::io;
+use std::vec;
+use std::vec::Vec;
+
+use crate::header::MAX_HEADER_LEN;
+use crate::{Chunk, ChunkError, Endianness, Header, MetaWidth};
+
+impl<'a, B: AsRef<[u8]>> Chunk<'a, B> {
+    /**
+     * Writes every chunk framed by `encode_into` to w, returns total bytes written.
+     * With flush set, w is flushed after each chunk.
+     * Framing errors are reported as `io::ErrorKind::InvalidInput`
+     */
+    pub fn write_all_to<W: io::Write>(&self, w: &mut W, flush: bool) -> io::Result<usize> {
+        let mut frame = vec![0; self.max_chunk_size];
+        let mut written = 0;
+        for counter in 0..self.total_chunks() {
+            let len = self
+                .encode_into(counter, &mut frame)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+            w.write_all(&frame[..len])?;
+            if flush {
+                w.flush()?;
+            }
+            written += len;
+        }
+        Ok(written)
+    }
+}
+
+/**
+ * Reads one message written by `Chunk::write_all_to` with default options
+ * and returns its topic and data.
+ * Frames carry no length on the wire, so max_chunk_size must match the sender.
+ * A header declaring more than max_message_len bytes fails with
+ * `MessageTooLarge` before the data is allocated, like
+ * `Dechunker::with_max_message_len`.
+ * Frames are expected in order, a closed stream gives `UnexpectedEof`
+ */
+pub fn read_message<R: io::Read>(
+    r: &mut R,
+    max_chunk_size: usize,
+    max_message_len: usize,
+) -> Result<(u8, Vec<u8>), ChunkError> {
+    let meta_width = MetaWidth::default();
+    let mut meta = [0; core::mem::size_of::<u64>()];
+    let meta = &mut meta[..meta_width.size()];
+    let mut header = [0; MAX_HEADER_LEN];
+    let header = &mut header[..1 + meta_width.size()];
+
+    r.read_exact(meta)?;
+    expect_counter(meta_width.decode(meta)?, 0)?;
+    r.read_exact(header)?;
+    let (topic, len) = Header::parse(header, meta_width, Endianness::Little)?;
+    if len > max_message_len {
+        return Err(ChunkError::MessageTooLarge {
+            max: max_message_len,
+            got: len,
+        });
+    }
+
+    let mut data = vec![0; len];
+    let ranges: Vec<_> = {
+        let chunk = Chunk::new(max_chunk_size, topic, &data);
+        chunk.validate()?;
+        (0..chunk.total_chunks())
+            .map_while(|counter| chunk.range(counter))
+            .collect()
+    };
+    for (counter, range) in ranges.into_iter().enumerate() {
+        if counter > 0 {
+            r.read_exact(meta)?;
+            expect_counter(meta_width.decode(meta)?, counter)?;
+        }
+        // read_exact retries short reads and `Interrupted`
+        r.read_exact(&mut data[range])?;
+    }
+    Ok((topic, data))
+}
+
+fn expect_counter(got: usize, expected: usize) -> Result<(), ChunkError> {
+    if got != expected {
+        return Err(ChunkError::UnexpectedChunkNumber {
+            expected: Some(expected),
+            got,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dechunker;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_write_all_to() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        let mut out = Vec::new();
+        let written = chunk.write_all_to(&mut out, true).unwrap();
+        assert_eq!(written, out.len());
+        assert_eq!(written, data.len() + 5 * (8 + 2) + 9);
+
+        let mut dechunker = Dechunker::new().with_crc();
+        let mut result = None;
+        let mut pos = 0;
+        for counter in 0..chunk.total_chunks() {
+            let mut frame = [0; 250];
+            let len = chunk.encode_into(counter, &mut frame).unwrap();
+            assert_eq!(out[pos..pos + len], frame[..len]);
+            result = dechunker.push(&out[pos..pos + len]).unwrap();
+            pos += len;
+        }
+        assert_eq!(result.unwrap(), data);
+    }
+
+    /**
+     * Hands out at most 7 bytes per read
+     */
+    struct Trickle<'a>(&'a [u8]);
+
+    impl io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(7);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_read_message() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut out = Vec::new();
+        Chunk::new(250, 0x10, &data)
+            .write_all_to(&mut out, false)
+            .unwrap();
+        let (topic, result) = read_message(&mut Trickle(&out), 250, 1000).unwrap();
+        assert_eq!(topic, 0x10);
+        assert_eq!(result, data);
+
+        assert!(matches!(
+            read_message(&mut Trickle(&out[..out.len() - 1]), 250, 1000),
+            Err(ChunkError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            read_message(&mut Trickle(&out[250..]), 250, 1000),
+            Err(ChunkError::UnexpectedChunkNumber {
+                expected: Some(0),
+                got: 1
+            })
+        ));
+        assert!(matches!(
+            read_message(&mut Trickle(&out), 250, 999),
+            Err(ChunkError::MessageTooLarge {
+                max: 999,
+                got: 1000
+            })
+        ));
+
+        let mut huge = [0; 17];
+        huge[9..].copy_from_slice(&(u64::MAX >> 1).to_le_bytes());
+        assert!(matches!(
+            read_message(&mut &huge[..], 250, 1 << 20),
+            Err(ChunkError::MessageTooLarge { max: 0x10_0000, .. })
+        ));
+    }
+
+    struct Broken;
+
+    impl io::Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "link down"))
+        }
+    }
+
+    #[test]
+    fn test_read_message_io_error() {
+        let err = read_message(&mut Broken, 250, 1000).unwrap_err();
+        assert!(
+            matches!(&err, ChunkError::Io(err) if err.kind() == io::ErrorKind::ConnectionReset)
+        );
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "link down");
+        assert_eq!(err.to_string(), "io error: link down");
+    }
+}
@@ -0,0 +1,93 @@
+use core::iter::FusedIterator;
+
+use crate::Chunk;
+
+/**
+ * Iterator over chunks of a borrowed `Chunk`, from the first chunk
+ * regardless of the chunk's own position, which stays untouched
+ */
+pub struct ChunkIter<'c, 'a, B = &'a [u8]> {
+    chunk: &'c Chunk<'a, B>,
+    counter: usize,
+}
+
+impl<'a, B: AsRef<[u8]>> Chunk<'a, B> {
+    /**
+     * Borrowing iterator from the first chunk, same as `&chunk` in a `for` loop
+     */
+    pub fn iter(&self) -> ChunkIter<'_, 'a, B> {
+        ChunkIter {
+            chunk: self,
+            counter: 0,
+        }
+    }
+}
+
+impl<'c, 'a, B: AsRef<[u8]>> Iterator for ChunkIter<'c, 'a, B> {
+    type Item = (&'c [u8], usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = self.chunk.chunk(Some(self.counter))?;
+        self.counter += 1;
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.chunk.total_chunks().saturating_sub(self.counter);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'c, 'a, B: AsRef<[u8]>> ExactSizeIterator for ChunkIter<'c, 'a, B> {}
+
+impl<'c, 'a, B: AsRef<[u8]>> FusedIterator for ChunkIter<'c, 'a, B> {}
+
+impl<'c, 'a, B: AsRef<[u8]>> IntoIterator for &'c Chunk<'a, B> {
+    type Item = (&'c [u8], usize);
+    type IntoIter = ChunkIter<'c, 'a, B>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_iter_by_ref() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut chunk = Chunk::new(250, 0x10, &data);
+        chunk.next();
+        let mut passes = 0;
+        for _ in 0..2 {
+            let mut collected = Vec::new();
+            for (payload, _) in &chunk {
+                collected.extend_from_slice(payload);
+            }
+            assert_eq!(collected, data);
+            passes += 1;
+        }
+        assert_eq!(passes, 2);
+        assert_eq!((&chunk).into_iter().len(), 5);
+        assert_eq!(chunk.counter(), 1);
+
+        let owned = Chunk::from_data(250, 0x10, vec![0; 1000]);
+        assert_eq!((&owned).into_iter().count(), 5);
+    }
+
+    #[test]
+    fn test_iter() {
+        let data = [0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let wire_size = chunk.wire_size();
+        assert_eq!(chunk.iter().count(), 5);
+        assert_eq!(chunk.iter().count(), 5);
+        assert_eq!(chunk.wire_size(), wire_size);
+        assert_eq!(chunk.counter(), 0);
+        assert!(chunk.iter().eq(&chunk));
+    }
+}
@@ -1,19 +1,196 @@
-#[derive(Default)]
-pub struct Chunk<'a> {
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+/**
+ * `log::trace!` with the `trace` feature, compiled out without it
+ */
+#[cfg(feature = "trace")]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        log::trace!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "trace"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(feature = "tokio")]
+mod async_io;
+mod builder;
+#[cfg(feature = "bytes")]
+mod bytes_chunker;
+mod chunk_n;
+#[cfg(feature = "compress")]
+mod compress;
+mod crc;
+#[cfg(feature = "alloc")]
+mod dechunker;
+mod dechunker_ref;
+#[cfg(feature = "std")]
+mod file;
+mod format;
+mod frame;
+mod header;
+#[cfg(feature = "std")]
+mod io;
+mod iter;
+mod meta;
+#[cfg(feature = "alloc")]
+mod mux;
+#[cfg(feature = "alloc")]
+mod owned;
+mod retry;
+#[cfg(feature = "alloc")]
+mod scatter;
+mod session;
+#[cfg(feature = "alloc")]
+mod stream;
+#[cfg(feature = "testing")]
+mod testing;
+
+pub use builder::ChunkBuilder;
+#[cfg(feature = "bytes")]
+pub use bytes_chunker::BytesChunker;
+pub use chunk_n::ChunkN;
+#[cfg(feature = "crc8")]
+pub use crc::Crc8;
+pub use crc::{crc16, crc32, verify_checksum, verify_crc, Checksum, Crc16, Crc32};
+#[cfg(feature = "alloc")]
+pub use dechunker::{Dechunker, SinkDechunker};
+pub use dechunker_ref::DechunkerRef;
+#[cfg(feature = "std")]
+pub use file::FileChunker;
+pub use frame::{ChunkLayout, Frame};
+pub use header::{
+    find_header, header_len, Header, HeaderFlags, FLAG_CHUNK_COUNT, FLAG_CHUNK_LENGTH,
+    FLAG_COMPRESSED, FLAG_CRC, FLAG_HAS_SESSION_ID, FLAG_LAST_CHUNK, FLAG_MESSAGE_CRC,
+};
+#[cfg(feature = "std")]
+pub use io::read_message;
+pub use iter::ChunkIter;
+pub use meta::{CounterWidth, Endianness, LengthWidth, MetaWidth};
+#[cfg(feature = "alloc")]
+pub use mux::{Demultiplexer, Multiplexer};
+#[cfg(feature = "alloc")]
+pub use owned::OwnedChunkIter;
+pub use retry::{RetryPolicy, Timestamp};
+#[cfg(feature = "alloc")]
+pub use scatter::ScatterChunk;
+pub use session::{ChunkSession, MAX_WINDOW};
+#[cfg(feature = "alloc")]
+pub use stream::StreamChunker;
+#[cfg(feature = "testing")]
+pub use testing::verify_roundtrip;
+
+pub(crate) const SESSION_ID_SIZE: usize = core::mem::size_of::<u16>();
+pub(crate) const PADDING_LEN_SIZE: usize = core::mem::size_of::<u8>();
+/**
+ * Largest `Chunk::with_alignment`, the padding length has to fit a byte
+ */
+pub const MAX_ALIGNMENT: usize = 256;
+/**
+ * Bytes of the default header: the topic and the length at the default
+ * `MetaWidth::U64`, the whole `overhead()` of the first frame together with
+ * the counter for `Chunk::new` without further options
+ */
+pub const BASE_HEADER_LEN: usize = core::mem::size_of::<u64>() + 1;
+
+/**
+ * Splits data into chunks of max_chunk_size, data is anything `AsRef<[u8]>`:
+ * a borrowed slice by default, or an owned `Vec<u8>`, `Box<[u8]>` and alike
+ */
+#[derive(Default, Clone)]
+pub struct Chunk<'a, B = &'a [u8]> {
     counter: usize,
+    back: Option<usize>,
     topic: u8,
-    data: &'a [u8],
+    data: B,
     max_chunk_size: usize,
-    meta_size: usize,
+    length_width: LengthWidth,
+    counter_width: CounterWidth,
+    varint: bool,
+    crc_size: usize,
+    checksum: Option<&'a dyn Checksum>,
+    version: Option<u8>,
+    compressed: bool,
+    last_flag: bool,
+    session_id: Option<u16>,
+    message_crc: Option<u32>,
+    chunk_length: bool,
+    chunk_count: bool,
+    repeat_header: bool,
+    terminator: bool,
+    alignment: Option<usize>,
+    header_flags: bool,
+    endianness: Endianness,
+    priority: u8,
+    /**
+     * Length of the data of `gathered()`, data itself is empty then
+     * and only the layout is taken from here
+     */
+    gathered_len: Option<usize>,
     pub status: ChunkStatus,
+    marker: PhantomData<&'a [u8]>,
 }
 
-#[derive(Debug)]
+/**
+ * Stable wire byte: `Sent` is 1, `Received` 2, 0 stands for no status
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "status")
+)]
 pub enum ChunkSessionStatus {
-    Sended,
-    Received,
+    #[cfg_attr(feature = "serde", serde(alias = "Sended"))]
+    Sent = 1,
+    Received = 2,
+    /**
+     * Terminal state of `ChunkSession::abort`
+     */
+    Aborted = 3,
+}
+
+impl ChunkSessionStatus {
+    #[deprecated(note = "renamed to `ChunkSessionStatus::Sent`")]
+    #[allow(non_upper_case_globals)]
+    pub const Sended: ChunkSessionStatus = ChunkSessionStatus::Sent;
+}
+
+impl From<&ChunkSessionStatus> for u8 {
+    fn from(status: &ChunkSessionStatus) -> u8 {
+        status.clone() as u8
+    }
+}
+
+impl TryFrom<u8> for ChunkSessionStatus {
+    type Error = ChunkError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            1 => Ok(ChunkSessionStatus::Sent),
+            2 => Ok(ChunkSessionStatus::Received),
+            3 => Ok(ChunkSessionStatus::Aborted),
+            _ => Err(ChunkError::InvalidStatus(byte)),
+        }
+    }
 }
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChunkStatus {
     pub number: Option<usize>,
     pub session: Option<ChunkSessionStatus>,
@@ -21,9 +198,186 @@ pub struct ChunkStatus {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum ChunkError {
-    InvalidMetaSize,
+    InvalidMetaSize {
+        expected: usize,
+        got: usize,
+    },
     OverflowRetryCounter,
+    LengthMismatch {
+        expected: usize,
+        got: usize,
+    },
+    UnexpectedChunkNumber {
+        expected: Option<usize>,
+        got: usize,
+    },
+    CrcMismatch,
+    CounterOutOfRange,
+    ChunkSizeTooSmall {
+        min: usize,
+    },
+    NoChunkInFlight,
+    InvalidTopic(u8),
+    UnexpectedEof,
+    UnsupportedVersion(u8),
+    #[cfg(feature = "compress")]
+    InflateFailed,
+    MissingFinalChunk,
+    SessionMismatch {
+        expected: u16,
+        got: u16,
+    },
+    MessageCrcMismatch,
+    TruncatedChunk {
+        expected: usize,
+        got: usize,
+    },
+    FrameTooLarge {
+        max: usize,
+        got: usize,
+    },
+    BufferTooSmall {
+        expected: usize,
+        got: usize,
+    },
+    DataTooLarge {
+        max: usize,
+    },
+    UnknownFlags(u8),
+    FlagsMismatch {
+        expected: u8,
+        got: u8,
+    },
+    ChunkCountMismatch {
+        total: usize,
+        got: usize,
+    },
+    UnexpectedTopic(u8),
+    InvalidStatus(u8),
+    Aborted,
+    InvalidAlignment(usize),
+    MessageTooLarge {
+        max: usize,
+        got: usize,
+    },
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkError::InvalidMetaSize { expected, got } => {
+                write!(
+                    f,
+                    "invalid meta size, expected {} bytes, got {}",
+                    expected, got
+                )
+            }
+            ChunkError::OverflowRetryCounter => write!(f, "retry counter overflow"),
+            ChunkError::LengthMismatch { expected, got } => {
+                write!(
+                    f,
+                    "data length mismatch, expected {}, got {}",
+                    expected, got
+                )
+            }
+            ChunkError::UnexpectedChunkNumber { expected, got } => match expected {
+                Some(expected) => {
+                    write!(
+                        f,
+                        "unexpected chunk number, current {}, received {}",
+                        expected, got
+                    )
+                }
+                None => write!(f, "unexpected chunk number {}, nothing was sent", got),
+            },
+            ChunkError::CrcMismatch => write!(f, "chunk CRC mismatch"),
+            ChunkError::CounterOutOfRange => write!(f, "chunk counter out of range"),
+            ChunkError::ChunkSizeTooSmall { min } => {
+                write!(
+                    f,
+                    "chunk size is too small, at least {} bytes required",
+                    min
+                )
+            }
+            ChunkError::NoChunkInFlight => write!(f, "no chunk is waiting for ack"),
+            ChunkError::InvalidTopic(topic) => write!(f, "invalid topic {}", topic),
+            ChunkError::UnexpectedEof => write!(f, "stream ended before the message was complete"),
+            ChunkError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            #[cfg(feature = "compress")]
+            ChunkError::InflateFailed => write!(f, "failed to inflate compressed data"),
+            ChunkError::MissingFinalChunk => write!(f, "stream ended without the final chunk"),
+            ChunkError::SessionMismatch { expected, got } => {
+                write!(f, "chunk of session {}, expected session {}", got, expected)
+            }
+            ChunkError::MessageCrcMismatch => write!(f, "reassembled message CRC mismatch"),
+            ChunkError::TruncatedChunk { expected, got } => {
+                write!(
+                    f,
+                    "truncated chunk, expected {} payload bytes, got {}",
+                    expected, got
+                )
+            }
+            ChunkError::FrameTooLarge { max, got } => {
+                write!(f, "frame of {} bytes exceeds max chunk size {}", got, max)
+            }
+            ChunkError::BufferTooSmall { expected, got } => {
+                write!(f, "buffer of {} bytes can't hold {} bytes", got, expected)
+            }
+            ChunkError::DataTooLarge { max } => {
+                write!(f, "data too large for the meta width, max {} bytes", max)
+            }
+            ChunkError::UnknownFlags(bits) => write!(f, "unknown header flags {:#04x}", bits),
+            ChunkError::FlagsMismatch { expected, got } => {
+                write!(f, "header flags {:#04x}, expected {:#04x}", got, expected)
+            }
+            ChunkError::ChunkCountMismatch { total, got } => {
+                write!(f, "chunk {} of a message of {} chunks", got, total)
+            }
+            ChunkError::UnexpectedTopic(topic) => write!(f, "topic {} not allowed", topic),
+            ChunkError::InvalidStatus(byte) => write!(f, "invalid status byte {:#04x}", byte),
+            ChunkError::Aborted => write!(f, "transfer aborted by the sender"),
+            ChunkError::InvalidAlignment(alignment) => write!(f, "invalid alignment {}", alignment),
+            ChunkError::MessageTooLarge { max, got } => {
+                write!(f, "message of {} bytes exceeds max length {}", got, max)
+            }
+            #[cfg(feature = "std")]
+            ChunkError::Io(err) => write!(f, "io error: {}", err),
+        }
+    }
+}
+
+/**
+ * `std::error::Error` is a re-export of this trait, so the impl covers std builds
+ */
+impl core::error::Error for ChunkError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            ChunkError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * A stream closed early is `UnexpectedEof`, anything else is kept as `Io`
+ */
+#[cfg(feature = "std")]
+impl From<std::io::Error> for ChunkError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => ChunkError::UnexpectedEof,
+            _ => ChunkError::Io(err),
+        }
+    }
 }
 
 impl ChunkStatus {
@@ -34,144 +388,2903 @@ impl ChunkStatus {
     }
 
     pub fn to_send(&mut self, number: usize) {
+        trace!("chunk {} sent", number);
         self.number = Some(number);
-        self.session = Some(ChunkSessionStatus::Sended);
+        self.session = Some(ChunkSessionStatus::Sent);
         self.retry = 0;
     }
 
-    pub fn to_received(&mut self, number: usize) {
+    pub fn to_received(&mut self, number: usize) -> Result<(), ChunkError> {
         if self.number != Some(number) {
-            panic!(
-                "Invalid chunk number, current {}, received {}",
-                self.number.unwrap(),
-                number
-            );
+            return Err(ChunkError::UnexpectedChunkNumber {
+                expected: self.number,
+                got: number,
+            });
         }
+        trace!("chunk {} received after {} retries", number, self.retry);
         self.session = Some(ChunkSessionStatus::Received);
         self.retry = 0;
+        Ok(())
     }
 
     pub fn increase_retry(&mut self) -> Result<u8, ChunkError> {
-        println!("<---Counter increase: {}", self.retry);
         if self.retry == u8::MAX {
             return Err(ChunkError::OverflowRetryCounter);
         }
         self.retry += 1;
+        trace!("chunk {:?} retry {}", self.number, self.retry);
         Ok(self.retry)
     }
+
+    /**
+     * Retries left before max is reached, 0 once it is
+     */
+    pub fn remaining_retries(&self, max: u8) -> u8 {
+        max.saturating_sub(self.retry)
+    }
+
+    pub fn reset_retry(&mut self) {
+        self.retry = 0;
+    }
+}
+
+/**
+ * Bit of the status byte set once the retries of a chunk are exhausted
+ */
+pub const STATUS_RETRIES_EXHAUSTED: u8 = 1 << 7;
+
+/**
+ * Compact status for ack frames: the `ChunkSessionStatus` byte (0 without
+ * session) in the low bits, `STATUS_RETRIES_EXHAUSTED` once retry reached `u8::MAX`.
+ * The chunk number and the exact retry count aren't carried
+ */
+impl From<&ChunkStatus> for u8 {
+    fn from(status: &ChunkStatus) -> u8 {
+        let session = status.session.as_ref().map_or(0, u8::from);
+        match status.retry {
+            u8::MAX => session | STATUS_RETRIES_EXHAUSTED,
+            _ => session,
+        }
+    }
+}
+
+/**
+ * Parses the byte of `From<&ChunkStatus>`, number is None and retry
+ * `u8::MAX` or 0, unknown bytes fail with `InvalidStatus`
+ */
+impl TryFrom<u8> for ChunkStatus {
+    type Error = ChunkError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        let session = match byte & !STATUS_RETRIES_EXHAUSTED {
+            0 => None,
+            session => Some(
+                ChunkSessionStatus::try_from(session)
+                    .map_err(|_| ChunkError::InvalidStatus(byte))?,
+            ),
+        };
+        let retry = match byte & STATUS_RETRIES_EXHAUSTED {
+            0 => 0,
+            _ => u8::MAX,
+        };
+        Ok(ChunkStatus {
+            number: None,
+            session,
+            retry,
+        })
+    }
 }
 
 impl<'a> Chunk<'a> {
-    pub fn new(max_chunk_size: usize, topic: u8, data: &'a [u8]) -> Self {
+    /**
+     * topic is a plain u8 or anything convertible into it,
+     * e.g. a `#[repr(u8)]` enum with `From<Enum> for u8`
+     */
+    pub fn new(max_chunk_size: usize, topic: impl Into<u8>, data: &'a [u8]) -> Self {
+        Chunk::from_data(max_chunk_size, topic, data)
+    }
+
+    /**
+     * Same as `new`, but fails with `ChunkSizeTooSmall` when max_chunk_size
+     * can't hold the framing overhead plus at least one payload byte,
+     * with `DataTooLarge` when data exceeds `max_data_len()`.
+     * Options enabled afterwards (e.g. `with_crc`) grow the overhead,
+     * use `builder()` to validate the final configuration.
+     */
+    pub fn try_new(
+        max_chunk_size: usize,
+        topic: impl Into<u8>,
+        data: &'a [u8],
+    ) -> Result<Self, ChunkError> {
+        let chunk = Chunk::new(max_chunk_size, topic, data);
+        chunk.validate()?;
+        Ok(chunk)
+    }
+
+    pub fn builder() -> ChunkBuilder<'a> {
+        ChunkBuilder::new()
+    }
+
+    /**
+     * Chunk of len bytes without the data, for `ScatterChunk`, `StreamChunker`
+     * and `FileChunker` filling payloads in through `encode_frame_with`
+     */
+    #[cfg(feature = "alloc")]
+    pub(crate) fn gathered(max_chunk_size: usize, topic: u8, len: usize) -> Self {
+        let mut chunk = Chunk::new(max_chunk_size, topic, &[]);
+        chunk.gathered_len = Some(len);
+        chunk
+    }
+
+    /**
+     * Chunks the fragments as one message without copying them together first,
+     * see `ScatterChunk`, payloads straddling fragments are owned buffers
+     */
+    #[cfg(feature = "alloc")]
+    pub fn from_slices(
+        max_chunk_size: usize,
+        topic: impl Into<u8>,
+        slices: &'a [&'a [u8]],
+    ) -> ScatterChunk<'a> {
+        ScatterChunk::new(max_chunk_size, topic.into(), slices)
+    }
+
+    /**
+     * Reads topic and data length written by `header()` with the default meta width,
+     * see `Header::parse`
+     */
+    pub fn parse_header(bytes: &[u8]) -> Result<(u8, usize), ChunkError> {
+        Header::parse(bytes, MetaWidth::default(), Endianness::Little)
+    }
+
+    /**
+     * Reads header written with `with_version(version)` using the default meta width,
+     * fails with `UnsupportedVersion` when the leading byte differs
+     */
+    pub fn parse_versioned_header(bytes: &[u8], version: u8) -> Result<(u8, usize), ChunkError> {
+        Header::parse_versioned(bytes, version, MetaWidth::default(), Endianness::Little)
+    }
+
+    /**
+     * Reads header written with `with_header_flags()` using the default meta width,
+     * see `Header::parse_flagged`
+     */
+    pub fn parse_flagged_header(bytes: &[u8]) -> Result<(u8, HeaderFlags, usize), ChunkError> {
+        Header::parse_flagged(bytes, MetaWidth::default(), Endianness::Little)
+    }
+
+    /**
+     * Reads header written with `with_varint()`, see `Header::parse_varint`
+     */
+    pub fn parse_varint_header(bytes: &[u8]) -> Result<(u8, usize), ChunkError> {
+        Header::parse_varint(bytes)
+    }
+
+    /**
+     * Same as `parse_header`, with the topic converted into a user type,
+     * fails with `InvalidTopic` when the conversion is rejected
+     */
+    pub fn parse_header_as<T: TryFrom<u8>>(bytes: &[u8]) -> Result<(T, usize), ChunkError> {
+        let (topic, len) = Chunk::parse_header(bytes)?;
+        let topic = T::try_from(topic).map_err(|_| ChunkError::InvalidTopic(topic))?;
+        Ok((topic, len))
+    }
+
+    /**
+     * Reads chunk counter of the default meta width, see `MetaWidth::decode`
+     */
+    pub fn meta(resp: &[u8]) -> Result<usize, ChunkError> {
+        MetaWidth::default().decode(resp)
+    }
+}
+
+impl<'a, B: AsRef<[u8]>> Chunk<'a, B> {
+    /**
+     * Chunks any `AsRef<[u8]>` data, e.g. an owned `Vec<u8>` that
+     * is returned together with the `Chunk`
+     */
+    pub fn from_data(max_chunk_size: usize, topic: impl Into<u8>, data: B) -> Self {
         Chunk {
             counter: 0,
+            back: None,
             data,
-            topic,
+            topic: topic.into(),
             max_chunk_size,
-            meta_size: core::mem::size_of::<usize>(),
+            length_width: LengthWidth::default(),
+            counter_width: CounterWidth::default(),
+            varint: false,
+            crc_size: 0,
+            checksum: None,
+            version: None,
+            compressed: false,
+            last_flag: false,
+            session_id: None,
+            message_crc: None,
+            chunk_length: false,
+            chunk_count: false,
+            repeat_header: false,
+            terminator: false,
+            alignment: None,
+            header_flags: false,
+            endianness: Endianness::Little,
+            priority: 0,
+            gathered_len: None,
             status: ChunkStatus::new(),
+            marker: PhantomData,
         }
     }
 
     /**
-     * header must contain length of data and topic
+     * Same chunk over other storage of the same bytes
      */
-    pub fn header(&self) -> [u8; core::mem::size_of::<usize>() + 1] {
-        let len = self.data.len();
-        let mut header = [0; core::mem::size_of::<usize>() + 1];
-        header[..1].copy_from_slice(&self.topic.to_le_bytes());
-        header[1..].copy_from_slice(&len.to_le_bytes());
-        header
+    #[cfg(feature = "alloc")]
+    pub(crate) fn map_data<D: AsRef<[u8]>>(self, map: impl FnOnce(B) -> D) -> Chunk<'a, D> {
+        Chunk {
+            counter: self.counter,
+            back: self.back,
+            data: map(self.data),
+            topic: self.topic,
+            max_chunk_size: self.max_chunk_size,
+            length_width: self.length_width,
+            counter_width: self.counter_width,
+            varint: self.varint,
+            crc_size: self.crc_size,
+            checksum: self.checksum,
+            version: self.version,
+            compressed: self.compressed,
+            last_flag: self.last_flag,
+            session_id: self.session_id,
+            message_crc: self.message_crc,
+            chunk_length: self.chunk_length,
+            chunk_count: self.chunk_count,
+            repeat_header: self.repeat_header,
+            terminator: self.terminator,
+            alignment: self.alignment,
+            header_flags: self.header_flags,
+            endianness: self.endianness,
+            priority: self.priority,
+            gathered_len: self.gathered_len,
+            status: self.status,
+            marker: PhantomData,
+        }
     }
 
-
-    pub fn counter(&self) -> usize {
-        self.counter
+    pub(crate) fn validate(&self) -> Result<(), ChunkError> {
+        self.check_alignment()?;
+        if self.max_chunk_size < self.min_chunk_size() {
+            return Err(ChunkError::ChunkSizeTooSmall {
+                min: self.min_chunk_size(),
+            });
+        }
+        self.check_len()
     }
 
-    pub fn meta(resp: &[u8]) -> Result<usize, ChunkError> {
-        if resp.len() < core::mem::size_of::<usize>() {
-            return Err(ChunkError::InvalidMetaSize);
+    /**
+     * Alignment must be a power of two of at most max_chunk_size and `MAX_ALIGNMENT`
+     */
+    fn check_alignment(&self) -> Result<(), ChunkError> {
+        match self.alignment {
+            Some(alignment)
+                if !alignment.is_power_of_two()
+                    || alignment > self.max_chunk_size
+                    || alignment > MAX_ALIGNMENT =>
+            {
+                Err(ChunkError::InvalidAlignment(alignment))
+            }
+            _ => Ok(()),
         }
-        let meta: &[u8; core::mem::size_of::<usize>()] =
-            &resp[..core::mem::size_of::<usize>()].try_into().unwrap();
-        Ok(usize::from_le_bytes(*meta))
     }
 
-    fn inc_counter(&mut self) {
-        self.counter += 1;
+    /**
+     * Reserves room for a CRC-16 at the end of every chunk,
+     * the CRC covers header, meta and payload of that chunk
+     */
+    pub fn with_crc(self) -> Self {
+        self.with_checksum(&Crc16)
     }
 
-    fn get_pointer(&self, counter: usize) -> usize {
-        /*
-         * 0 iter = 0 * 250 - 4 * 0 = 0
-         * 1 iter = 1 * 250 - 4 * 1 - 8 = 238
-         * 2 iter = 2 * 250 - 4 * 2 - 8 = 484
-         */
-        if counter == 0 {
-            return 0;
-        }
-        counter * self.max_chunk_size - self.meta_size * counter - self.header().len()
+    /**
+     * Same as `with_crc` with any `Checksum`, `width()` bytes are reserved
+     * at the end of every chunk. The receiver needs the same one,
+     * see `Dechunker::with_checksum`
+     */
+    pub fn with_checksum(mut self, checksum: &'a dyn Checksum) -> Self {
+        self.crc_size = crc::checksum_width(checksum);
+        self.checksum = Some(checksum);
+        self
     }
 
-    fn start(&self, counter: Option<usize>) -> usize {
-        let counter = counter.unwrap_or(self.counter);
-        self.get_pointer(counter)
+    /**
+     * Width of the data length in the header and of the chunk counter in meta
+     */
+    pub fn with_meta_width(self, meta_width: MetaWidth) -> Self {
+        self.with_length_width(meta_width)
+            .with_counter_width(meta_width)
     }
 
-    fn end(&self, counter: Option<usize>) -> usize {
-        self.get_pointer(counter.unwrap_or(self.counter) + 1)
+    /**
+     * Width of the data length in the header only, it bounds the data length
+     */
+    pub fn with_length_width(mut self, length_width: LengthWidth) -> Self {
+        self.length_width = length_width;
+        self
     }
 
-    pub fn chunk(&self, counter: Option<usize>) -> Option<(&'a [u8], usize)> {
-        let start = self.start(counter);
-        if start > self.data.len() {
-            return None;
+    /**
+     * Width of the chunk counter in meta only, it bounds the number of chunks.
+     * The chunk count of `with_chunk_count()` and the chunk length of
+     * `with_chunk_length()` take it as well
+     */
+    pub fn with_counter_width(mut self, counter_width: CounterWidth) -> Self {
+        self.counter_width = counter_width;
+        self
+    }
+
+    /**
+     * Writes counter, header length and chunk length as LEB128 varints
+     * instead of meta width fields, for the smallest framing on reliable
+     * links, see `ChunkBuilder::minimal`. The counter takes the bytes of the
+     * last counter in every frame (padded), so all chunks keep one capacity,
+     * top-bit flags (`with_last_flag`, compression) go on top of the value.
+     * The receiver needs `Dechunker::with_varint`
+     */
+    pub fn with_varint(mut self) -> Self {
+        self.varint = true;
+        self
+    }
+
+    /**
+     * Byte order of the header length and of the meta counter, little-endian by default
+     */
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /**
+     * Prefixes the header with a magic/version byte, off by default.
+     * Receivers reject other values with `UnsupportedVersion`, so a layout change
+     * (meta width, CRC, ...) can't be silently misread by an old receiver.
+     * Recommended layout of the first frame with the version byte:
+     *
+     * [counter: meta][version: u8][topic: u8][len: meta][payload][crc]
+     */
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /**
+     * Marks the final chunk with the top bit of its meta counter, off by default.
+     * A receiver with `Dechunker::with_last_flag` then tells a lost tail
+     * from a transfer still in progress, counters are limited to the lower bits
+     */
+    pub fn with_last_flag(mut self) -> Self {
+        self.last_flag = true;
+        self
+    }
+
+    /**
+     * Puts a transfer id right after the counter of every frame, so a receiver
+     * tells apart chunks of messages sent back-to-back, see `Dechunker::with_session_ids`
+     */
+    pub fn with_session_id(mut self, session_id: u16) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    /**
+     * Scheduling priority, 0 by default. A `Multiplexer` sends chunks of the
+     * highest priority first and puts it on the wire, see `Multiplexer::push`,
+     * a single transfer is framed the same whatever its priority
+     */
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /**
+     * Puts the payload length of the chunk right after the counter of every frame,
+     * `MetaWidth` wide, so a receiver validates each frame on its own,
+     * see `Dechunker::with_chunk_length`
+     */
+    pub fn with_chunk_length(mut self) -> Self {
+        self.chunk_length = true;
+        self
+    }
+
+    /**
+     * Puts the counter of the final chunk after the data length of the header,
+     * meta wide, so a receiver knows the number of chunks from the first one,
+     * see `Dechunker::with_chunk_count`
+     */
+    pub fn with_chunk_count(mut self) -> Self {
+        self.chunk_count = true;
+        self
+    }
+
+    /**
+     * Puts `header()` into every chunk instead of the first one only, so a
+     * receiver joining late or having lost chunk 0 on a broadcast link still
+     * learns topic and length from any chunk. Every chunk then carries as much
+     * payload as the first one, see `Dechunker::with_repeat_header`
+     */
+    pub fn with_repeat_header(mut self) -> Self {
+        self.repeat_header = true;
+        self
+    }
+
+    /**
+     * Ends the message with one more frame without payload after the last
+     * data chunk, for transports expecting an explicit end marker. It counts
+     * in `total_chunks()` and `wire_size()`, see `Dechunker::with_terminator`.
+     * The terminator sets the top bit of its counter, the one of
+     * `with_last_flag()`, so counters are limited to the lower bits and the
+     * frame marks itself whatever the header carries
+     */
+    pub fn with_terminator(mut self) -> Self {
+        self.terminator = true;
+        self
+    }
+
+    /**
+     * Pads every frame with zero bytes after the payload to a multiple of
+     * alignment, e.g. for DMA engines moving whole words. The padding length
+     * goes into a byte after the session id, full chunks are max_chunk_size
+     * rounded down to alignment. Alignment must be a power of two of at most
+     * max_chunk_size and `MAX_ALIGNMENT`, encoding fails with `InvalidAlignment`
+     * otherwise, see `Dechunker::with_alignment`
+     */
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /**
+     * Appends CRC-32 of the whole data to the header, so the receiver verifies
+     * the reassembled message end-to-end, see `Dechunker::with_message_crc`.
+     * The CRC is computed here once, not per chunk
+     */
+    pub fn with_message_crc(mut self) -> Self {
+        self.message_crc = Some(crc::crc32(self.data()));
+        self
+    }
+
+    /**
+     * Puts the flags byte after the topic of the header, off by default.
+     * It records which options the message was framed with (see `flags()`),
+     * so a receiver with `Dechunker::with_header_flags` rejects a mismatching
+     * configuration with `FlagsMismatch` instead of misreading it.
+     * The compressed flag then moves from the top bit of the length to the flags byte
+     */
+    pub fn with_header_flags(mut self) -> Self {
+        self.header_flags = true;
+        self
+    }
+
+    /**
+     * Options this message is framed with, as written by `with_header_flags`
+     */
+    pub fn flags(&self) -> HeaderFlags {
+        let mut flags = HeaderFlags::default();
+        flags.set(FLAG_CRC, self.crc_size > 0);
+        flags.set(FLAG_COMPRESSED, self.compressed);
+        flags.set(FLAG_HAS_SESSION_ID, self.session_id.is_some());
+        flags.set(FLAG_LAST_CHUNK, self.last_flag);
+        flags.set(FLAG_MESSAGE_CRC, self.message_crc.is_some());
+        flags.set(FLAG_CHUNK_LENGTH, self.chunk_length);
+        flags.set(FLAG_CHUNK_COUNT, self.chunk_count);
+        flags
+    }
+
+    /**
+     * header must contain length of data and topic,
+     * length is truncated to the length width, `encode_meta` checks it fits.
+     * For compressed data the top bit of the length is set, see `new_compressed`,
+     * unless the flags byte of `with_header_flags` carries it
+     */
+    pub fn header(&self) -> Header {
+        let mut header = [0; header::MAX_HEADER_LEN];
+        let mut pos = 0;
+        if let Some(version) = self.version {
+            header[pos] = version;
+            pos += 1;
+        }
+        header[pos..pos + 1].copy_from_slice(&self.topic.to_le_bytes());
+        pos += 1;
+        if self.header_flags {
+            header[pos] = self.flags().bits();
+            pos += 1;
+        }
+        let mut len = self.data_len() as u64;
+        let len_size = self.len_size();
+        if self.varint {
+            if self.compressed && !self.header_flags {
+                len |= meta::varint_top_bit(len_size);
+            }
+            meta::put_varint(len, len_size, &mut header[pos..]);
+        } else {
+            if self.compressed && !self.header_flags {
+                len |= self.length_width.top_bit();
+            }
+            self.length_width
+                .put(len, self.endianness, &mut header[pos..]);
+        }
+        pos += len_size;
+        if self.chunk_count {
+            let meta_size = self.meta_size();
+            let last = self.total_chunks().saturating_sub(1) as u64;
+            if self.varint {
+                meta::put_varint(last, meta_size, &mut header[pos..]);
+            } else {
+                self.counter_width
+                    .put(last, self.endianness, &mut header[pos..]);
+            }
+            pos += meta_size;
         }
-        let end = self.end(counter);
-        if end > self.data.len() {
-            return Some((&self.data[start..], counter.unwrap_or(self.counter)));
+        if let Some(message_crc) = self.message_crc {
+            header[pos..pos + crc::MESSAGE_CRC_SIZE].copy_from_slice(&message_crc.to_le_bytes());
         }
-        Some((&self.data[start..end], counter.unwrap_or(self.counter)))
+        Header::new(header, self.header_len())
     }
-}
 
-impl<'a> Iterator for Chunk<'a> {
-    type Item = (&'a [u8], usize);
+    /**
+     * Smallest max_chunk_size that holds the framing and one payload byte
+     */
+    pub fn min_chunk_size(&self) -> usize {
+        let min = self.header_len() + self.frame_overhead() + 1;
+        min + self.padding(min)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.chunk(Some(self.counter)) {
-            Some(chunk) => {
-                self.inc_counter();
-                Some(chunk)
+    /**
+     * Bytes of the whole frame of counter carrying payload_len bytes,
+     * the padding of `with_alignment()` included
+     */
+    fn frame_len(&self, counter: usize, with_header: bool, payload_len: usize) -> usize {
+        let len = self.prefix_len(counter, with_header) + payload_len + self.crc_size;
+        len + self.padding(len)
+    }
+
+    /**
+     * Zero bytes rounding a frame of len up to `with_alignment()`
+     */
+    fn padding(&self, len: usize) -> usize {
+        match self.alignment {
+            Some(alignment) if alignment > 1 => (alignment - len % alignment) % alignment,
+            _ => 0,
+        }
+    }
+
+    /**
+     * max_chunk_size rounded down to `with_alignment()`
+     */
+    fn frame_room(&self) -> usize {
+        match self.alignment {
+            Some(alignment) if alignment.is_power_of_two() => {
+                self.max_chunk_size & !(alignment - 1)
             }
-            None => None,
+            _ => self.max_chunk_size,
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /**
+     * Bytes of the frame of counter preceding the payload,
+     * with_header unset leaves out the header of `includes_header()`
+     */
+    fn prefix_len(&self, counter: usize, with_header: bool) -> usize {
+        let header_len = if with_header && self.includes_header(counter) {
+            self.header_len()
+        } else {
+            0
+        };
+        self.frame_overhead() - self.crc_size + header_len
+    }
 
-    #[test]
-    fn test_chunk() {
-        let data = vec![0; 1000];
-        let chunk = Chunk::new(250, 0x10, &data);
-        let mut iter = chunk.into_iter();
-        assert_eq!(iter.next().unwrap().0.len() + core::mem::size_of::<usize>() * 2 + 1, 250);
-        assert_eq!(iter.next().unwrap().0.len() + core::mem::size_of::<usize>(), 250);
-        assert_eq!(iter.next().unwrap().0.len() + core::mem::size_of::<usize>(), 250);
+    /**
+     * Writes counter, chunk length, session id, padding length and header
+     * (first chunk only) of the frame of counter into out, which holds at least `prefix_len()`
+     */
+    fn encode_prefix(
+        &self,
+        counter: usize,
+        with_header: bool,
+        payload_len: usize,
+        out: &mut [u8],
+    ) -> Result<usize, ChunkError> {
+        let pos = self.encode_meta(counter, out)?;
+        let header = with_header && self.includes_header(counter);
+        self.encode_prefix_from(pos, header, payload_len, out)
     }
 
-    #[test]
-    fn test_header() {
-        let data = vec![0; 1000];
-        let chunk = Chunk::new(250, 0x10, &data);
-        let header = chunk.header();
+    /**
+     * `encode_prefix` past the counter written up to pos
+     */
+    fn encode_prefix_from(
+        &self,
+        mut pos: usize,
+        header: bool,
+        payload_len: usize,
+        out: &mut [u8],
+    ) -> Result<usize, ChunkError> {
+        let header_len = if header { self.header_len() } else { 0 };
+        if self.chunk_length && self.varint {
+            let size = self.chunk_length_size();
+            meta::put_varint(payload_len as u64, size, &mut out[pos..]);
+            pos += size;
+        } else if self.chunk_length {
+            pos += self
+                .counter_width
+                .write(payload_len, self.endianness, &mut out[pos..])?;
+        }
+        if let Some(session_id) = self.session_id {
+            let session_id = match self.endianness {
+                Endianness::Little => session_id.to_le_bytes(),
+                Endianness::Big => session_id.to_be_bytes(),
+            };
+            out[pos..pos + SESSION_ID_SIZE].copy_from_slice(&session_id);
+            pos += SESSION_ID_SIZE;
+        }
+        if self.alignment.is_some() {
+            self.check_alignment()?;
+            let unpadded = pos + PADDING_LEN_SIZE + header_len + payload_len + self.crc_size;
+            out[pos] = self.padding(unpadded) as u8;
+            pos += PADDING_LEN_SIZE;
+        }
+        if header {
+            out[pos..pos + header_len].copy_from_slice(&self.header());
+            pos += header_len;
+        }
+        Ok(pos)
+    }
 
-        assert_eq!(header.len(), core::mem::size_of::<usize>() + 1);
-        #[cfg(target_pointer_width = "64")]
-        assert_eq!(header, [0x10, 0xE8, 0x03, 0, 0, 0, 0, 0, 0]);
+    /**
+     * Bytes of every frame besides the header and payload
+     */
+    fn frame_overhead(&self) -> usize {
+        self.frame_overhead_in(self.meta_size())
+    }
+
+    fn frame_overhead_in(&self, meta_size: usize) -> usize {
+        let session_id_size = if self.session_id.is_some() {
+            SESSION_ID_SIZE
+        } else {
+            0
+        };
+        let padding_len_size = if self.alignment.is_some() {
+            PADDING_LEN_SIZE
+        } else {
+            0
+        };
+        meta_size + self.chunk_length_size() + session_id_size + padding_len_size + self.crc_size
+    }
+
+    /**
+     * Bytes of the counter in meta: the counter width, or with `with_varint()`
+     * the varint of the last counter
+     */
+    pub fn meta_size(&self) -> usize {
+        if !self.varint {
+            return self.counter_width.size();
+        }
+        // a wider counter leaves less payload per chunk, so more chunks
+        (1..meta::MAX_VARINT_LEN)
+            .find(|&size| {
+                let last = self.total_chunks_in(size).saturating_sub(1);
+                meta::varint_len(last as u64, self.flagged_counter()) <= size
+            })
+            .unwrap_or(meta::MAX_VARINT_LEN)
+    }
+
+    /**
+     * Bytes of the data length in the header
+     */
+    fn len_size(&self) -> usize {
+        if self.varint {
+            meta::varint_len(
+                self.data_len() as u64,
+                self.compressed && !self.header_flags,
+            )
+        } else {
+            self.length_width.size()
+        }
+    }
+
+    /**
+     * Bytes of the chunk length field of `with_chunk_length()`
+     */
+    fn chunk_length_size(&self) -> usize {
+        match (self.chunk_length, self.varint) {
+            (false, _) => 0,
+            (true, false) => self.counter_width.size(),
+            (true, true) => meta::varint_len(self.max_chunk_size as u64, false),
+        }
+    }
+
+    fn header_len(&self) -> usize {
+        self.header_len_in(self.meta_size())
+    }
+
+    fn header_len_in(&self, meta_size: usize) -> usize {
+        let chunk_count_size = if self.chunk_count { meta_size } else { 0 };
+        let message_crc_size = if self.message_crc.is_some() {
+            crc::MESSAGE_CRC_SIZE
+        } else {
+            0
+        };
+        self.version.is_some() as usize
+            + 1
+            + self.header_flags as usize
+            + self.len_size()
+            + chunk_count_size
+            + message_crc_size
+    }
+
+    /**
+     * Writes chunk counter with the configured meta width,
+     * fails when either counter or data length doesn't fit it
+     */
+    pub fn encode_meta(&self, counter: usize, out: &mut [u8]) -> Result<usize, ChunkError> {
+        self.check_len()?;
+        if self.varint {
+            return self.encode_varint_meta(counter, out);
+        }
+        if self.flagged_counter() && counter as u64 >= self.counter_width.top_bit() {
+            return Err(ChunkError::CounterOutOfRange);
+        }
+        let len = self.counter_width.write(counter, self.endianness, out)?;
+        if self.flagged_counter() && counter + 1 == self.total_chunks() {
+            self.counter_width.set_top_bit(self.endianness, out);
+        }
+        Ok(len)
+    }
+
+    fn encode_varint_meta(&self, counter: usize, out: &mut [u8]) -> Result<usize, ChunkError> {
+        let size = self.meta_size();
+        if meta::varint_len(counter as u64, self.flagged_counter()) > size {
+            return Err(ChunkError::CounterOutOfRange);
+        }
+        if out.len() < size {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: size,
+                got: out.len(),
+            });
+        }
+        let mut value = counter as u64;
+        if self.flagged_counter() && counter + 1 == self.total_chunks() {
+            value |= meta::varint_top_bit(size);
+        }
+        meta::put_varint(value, size, out);
+        Ok(size)
+    }
+
+    /**
+     * Data length must fit the header length field
+     */
+    fn check_len(&self) -> Result<(), ChunkError> {
+        let max = self.max_data_len();
+        if self.data_len() > max {
+            return Err(ChunkError::DataTooLarge { max });
+        }
+        Ok(())
+    }
+
+    /**
+     * Largest data length the header length field and the chunk counter
+     * can describe with the current meta width and options
+     */
+    pub fn max_data_len(&self) -> usize {
+        if self.varint {
+            return usize::MAX;
+        }
+        // the top bit of the header length is taken by the compressed flag
+        let len_max = if self.compressed && !self.header_flags {
+            self.length_width.top_bit() - 1
+        } else {
+            self.length_width.max()
+        };
+        // and the top bit of the counter by the last-chunk flag or the terminator,
+        // the highest counter is the one of the abort frame, the next one of the terminator
+        let counter_max = self.abort_counter() as u64 - 1 - self.terminator as u64;
+        let max = match (self.capacity(0), self.capacity(1)) {
+            (Some(first), Some(per_chunk)) => (per_chunk as u64)
+                .saturating_mul(counter_max)
+                .saturating_add(first as u64)
+                .min(len_max),
+            _ => len_max,
+        };
+        usize::try_from(max).unwrap_or(usize::MAX)
+    }
+
+    pub fn counter(&self) -> usize {
+        self.counter
+    }
+
+    /**
+     * Top bit of the counter is a flag: it marks the final chunk of
+     * `with_last_flag()` and the terminator of `with_terminator()`
+     */
+    fn flagged_counter(&self) -> bool {
+        self.last_flag || self.terminator
+    }
+
+    /**
+     * Counter of `encode_abort()`, no data chunk gets it
+     */
+    pub fn abort_counter(&self) -> usize {
+        meta::abort_counter(self.counter_width, self.flagged_counter(), self.varint)
+    }
+
+    pub fn topic(&self) -> u8 {
+        self.topic
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    pub fn max_chunk_size(&self) -> usize {
+        self.max_chunk_size
+    }
+
+    pub fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    /**
+     * Bytes of the message, the ones of `data()` unless gathered by `ScatterChunk`
+     */
+    fn data_len(&self) -> usize {
+        self.gathered_len.unwrap_or_else(|| self.data().len())
+    }
+
+    /**
+     * Restarts iteration from the first chunk and clears status
+     */
+    pub fn reset(&mut self) {
+        self.counter = 0;
+        self.back = None;
+        self.status = ChunkStatus::new();
+    }
+
+    /**
+     * Cheap sanity check of a received frame framed with the options of this chunk:
+     * fails with `InvalidMetaSize` when it is too short for the counter,
+     * with `UnexpectedChunkNumber` when the counter isn't expected_counter
+     * and with `FrameTooLarge` when it exceeds max_chunk_size
+     */
+    pub fn validate_frame(
+        &self,
+        frame: &[u8],
+        expected_counter: Option<usize>,
+    ) -> Result<(), ChunkError> {
+        let counter = if self.varint {
+            meta::read_varint_flagged(frame, self.flagged_counter())?.0
+        } else if self.flagged_counter() {
+            self.counter_width.read_flagged(frame, self.endianness)?.0
+        } else {
+            self.counter_width.read(frame, self.endianness)?
+        };
+        if let Some(expected) = expected_counter {
+            if counter != expected {
+                return Err(ChunkError::UnexpectedChunkNumber {
+                    expected: Some(expected),
+                    got: counter,
+                });
+            }
+        }
+        if frame.len() > self.max_chunk_size {
+            return Err(ChunkError::FrameTooLarge {
+                max: self.max_chunk_size,
+                got: frame.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /**
+     * Changes max_chunk_size, e.g. after a link negotiated its MTU, and restarts
+     * iteration like `reset()`. Chunk boundaries move with the size, so counters
+     * emitted before no longer match and the whole message has to be resent.
+     * Fails with `ChunkSizeTooSmall` (or `DataTooLarge`) leaving the chunk untouched
+     */
+    pub fn set_max_chunk_size(&mut self, size: usize) -> Result<(), ChunkError> {
+        let previous = core::mem::replace(&mut self.max_chunk_size, size);
+        if let Err(err) = self.validate() {
+            self.max_chunk_size = previous;
+            return Err(err);
+        }
+        self.reset();
+        Ok(())
+    }
+
+    /**
+     * Changes the topic, e.g. to reuse a pooled chunk for another message type,
+     * and restarts iteration like `reset()`: the topic lives in the header of
+     * the first chunk, so it only takes effect on the next full iteration
+     */
+    pub fn set_topic(&mut self, topic: u8) {
+        self.topic = topic;
+        self.reset();
+    }
+
+    /**
+     * Positions iteration at the given chunk, e.g. for selective retransmission
+     */
+    pub fn seek(&mut self, counter: usize) {
+        self.counter = counter;
+    }
+
+    /**
+     * Continues a transfer interrupted e.g. by a reboot: the next chunk
+     * yielded is counter, the one after the last acked chunk persisted
+     * together with `status`, which is left as it is. Fails with
+     * `CounterOutOfRange` unless counter is below `total_chunks()`,
+     * a counter saved for other data
+     */
+    pub fn resume_from(&mut self, counter: usize) -> Result<(), ChunkError> {
+        if counter >= self.total_chunks() {
+            return Err(ChunkError::CounterOutOfRange);
+        }
+        self.counter = counter;
+        Ok(())
+    }
+
+    /**
+     * Number of chunks the iterator yields for the whole data,
+     * 1 for data fitting `first_chunk_capacity()`, empty data included
+     */
+    pub fn total_chunks(&self) -> usize {
+        self.total_chunks_in(self.meta_size())
+    }
+
+    fn total_chunks_in(&self, meta_size: usize) -> usize {
+        let (first, per_chunk) = match (
+            self.capacity_in(0, meta_size),
+            self.capacity_in(1, meta_size),
+        ) {
+            (Some(first), Some(per_chunk)) => (first, per_chunk),
+            _ => return 0,
+        };
+        let terminator = self.terminator as usize;
+        if self.data_len() <= first {
+            return 1 + terminator;
+        }
+        1 + (self.data_len() - first).div_ceil(per_chunk) + terminator
+    }
+
+    fn inc_counter(&mut self) {
+        self.counter += 1;
+    }
+
+    /**
+     * Bytes of all frames of `encode_into` together: header once (or for every
+     * chunk with `with_repeat_header()`), meta, CRC and other per-frame fields
+     * for every chunk, and the payload.
+     * 0 when max_chunk_size can't hold the framing
+     */
+    pub fn wire_size(&self) -> usize {
+        let total = self.total_chunks();
+        let headers = if self.repeat_header { total } else { 1 };
+        if self.alignment.is_some() {
+            return (0..total)
+                .map_while(|counter| {
+                    Some(self.frame_len(counter, true, self.range(counter)?.len()))
+                })
+                .sum();
+        }
+        match total {
+            0 => 0,
+            total => total * self.frame_overhead() + headers * self.header_len() + self.data_len(),
+        }
+    }
+
+    /**
+     * Chunks from here on were already yielded by `next_back()`
+     */
+    fn back_counter(&self) -> usize {
+        self.back.unwrap_or_else(|| self.total_chunks())
+    }
+
+    /**
+     * Counter of the chunk carrying the data byte at offset, the inverse
+     * of `Frame::data_offset`, None when offset is past the data
+     * or max_chunk_size can't hold the framing
+     */
+    pub fn chunk_for_offset(&self, offset: usize) -> Option<usize> {
+        if offset >= self.data_len() {
+            return None;
+        }
+        let first = self.capacity(0)?;
+        if offset < first {
+            return Some(0);
+        }
+        let per_chunk = self.capacity(1).filter(|&per_chunk| per_chunk > 0)?;
+        Some(1 + (offset - first) / per_chunk)
+    }
+
+    /**
+     * Only the first chunk carries `header()` after its framing prefix,
+     * every later chunk goes with the counter (and the other per-frame fields) only,
+     * unless `with_repeat_header()` puts it into every chunk
+     */
+    pub fn includes_header(&self, counter: usize) -> bool {
+        counter == 0 || self.repeat_header
+    }
+
+    /**
+     * Bytes of the first frame besides its payload: `header()` and meta,
+     * plus the CRC, session id and other per-frame fields when set.
+     * Alignment padding isn't included, it depends on the payload length
+     */
+    pub fn overhead(&self) -> usize {
+        self.header_len() + self.frame_overhead()
+    }
+
+    /**
+     * Payload bytes of the first chunk: max_chunk_size without meta, header and CRC,
+     * 0 when max_chunk_size can't hold the framing
+     */
+    pub fn first_chunk_capacity(&self) -> usize {
+        self.capacity(0).unwrap_or(0)
+    }
+
+    /**
+     * Payload bytes of every chunk after the first one: max_chunk_size without
+     * meta and CRC (and header with `with_repeat_header()`),
+     * 0 when max_chunk_size can't hold the framing
+     */
+    pub fn chunk_capacity(&self) -> usize {
+        self.capacity(1).unwrap_or(0)
+    }
+
+    /**
+     * Payload bytes the chunk can carry, None when max_chunk_size
+     * can't even hold the framing
+     */
+    fn capacity(&self, counter: usize) -> Option<usize> {
+        self.capacity_in(counter, self.meta_size())
+    }
+
+    fn capacity_in(&self, counter: usize, meta_size: usize) -> Option<usize> {
+        let per_chunk = self
+            .frame_room()
+            .checked_sub(self.frame_overhead_in(meta_size))?;
+        if self.includes_header(counter) {
+            return per_chunk.checked_sub(self.header_len_in(meta_size));
+        }
+        Some(per_chunk)
+    }
+
+    fn get_pointer(&self, counter: usize) -> Option<usize> {
+        /*
+         * first chunk 233, later 242 bytes with Chunk::new(250, ..):
+         * 0 iter = 0
+         * 1 iter = 233 + 0 * 242 = 233
+         * 2 iter = 233 + 1 * 242 = 475
+         */
+        if counter == 0 {
+            return Some(0);
+        }
+        (counter - 1)
+            .checked_mul(self.capacity(1)?)?
+            .checked_add(self.capacity(0)?)
+    }
+
+    fn start(&self, counter: Option<usize>) -> Option<usize> {
+        let counter = counter.unwrap_or(self.counter);
+        self.get_pointer(counter)
+    }
+
+    fn end(&self, counter: Option<usize>) -> Option<usize> {
+        let counter = counter.unwrap_or(self.counter);
+        Some(
+            self.start(Some(counter))?
+                .saturating_add(self.capacity(counter)?),
+        )
+    }
+
+    /**
+     * Range of data carried by the chunk, None past the end of data.
+     * Only the first chunk and the terminator of `with_terminator()` may be
+     * empty, data ending exactly on a chunk boundary gives no empty trailing chunk
+     */
+    fn range(&self, counter: usize) -> Option<Range<usize>> {
+        if self.terminator && counter > 0 && counter + 1 == self.total_chunks() {
+            return Some(self.data_len()..self.data_len());
+        }
+        let start = self.start(Some(counter))?;
+        if start > self.data_len() || (counter > 0 && start == self.data_len()) {
+            return None;
+        }
+        let end = self.end(Some(counter))?;
+        Some(start..end.min(self.data_len()))
+    }
+
+    /**
+     * `range()` telling a misconfiguration (`validate()`) and a counter whose
+     * offset overflows (`CounterOutOfRange`) apart from the end of data
+     */
+    fn try_range(&self, counter: usize) -> Result<Option<Range<usize>>, ChunkError> {
+        self.validate()?;
+        if self.get_pointer(counter).is_none() {
+            return Err(ChunkError::CounterOutOfRange);
+        }
+        Ok(self.range(counter))
+    }
+
+    /**
+     * Payload of the chunk and its number, None past the end of data.
+     * The first chunk is always present: empty data gives exactly one
+     * header-only chunk, so the receiver still learns topic and zero length.
+     * A misconfigured chunk gives None as well, `try_chunk()` tells them apart
+     */
+    pub fn chunk(&self, counter: Option<usize>) -> Option<(&[u8], usize)> {
+        let counter = counter.unwrap_or(self.counter);
+        let range = self.try_range(counter).ok()??;
+        Some((&self.data()[range], counter))
+    }
+
+    /**
+     * All chunks of data as frames, independent of the iterator position
+     */
+    pub fn frames(&self) -> impl Iterator<Item = Frame<'_>> + '_ {
+        (0..).map_while(|counter| self.frame_in(counter, self.data()))
+    }
+
+    fn frame_in<'d>(&self, counter: usize, data: &'d [u8]) -> Option<Frame<'d>> {
+        let range = self.range(counter)?;
+        Some(Frame {
+            topic: self.topic,
+            counter,
+            session_id: self.session_id,
+            is_first: counter == 0,
+            is_last: counter + 1 == self.total_chunks(),
+            total_chunks: Some(self.total_chunks()),
+            data_offset: range.start,
+            payload: &data[range],
+        })
+    }
+
+    /**
+     * Every wire frame of `encode_into` in its own buffer, e.g. to queue them.
+     * A chunk that can't be encoded ends the list, `try_chunks()` reports why
+     */
+    #[cfg(feature = "alloc")]
+    pub fn to_frames(&self) -> alloc::vec::Vec<alloc::vec::Vec<u8>> {
+        self.framed().collect()
+    }
+
+    /**
+     * Lazy counterpart of `to_frames()`, every `next()` allocates exactly one
+     * complete wire frame, so it composes with `take()` or throttling
+     */
+    #[cfg(feature = "alloc")]
+    pub fn framed(&self) -> impl Iterator<Item = alloc::vec::Vec<u8>> + '_ {
+        (0..self.total_chunks()).map_while(|counter| self.frame_vec(counter, true))
+    }
+
+    /**
+     * `header()` framed on its own, followed by the checksum when one is set,
+     * for transports delivering it on a control channel, see `Dechunker::set_header`
+     */
+    #[cfg(feature = "alloc")]
+    pub fn header_frame(&self) -> alloc::vec::Vec<u8> {
+        let header = self.header();
+        let mut frame = alloc::vec![0; header.len() + self.crc_size];
+        frame[..header.len()].copy_from_slice(&header);
+        if let Some(checksum) = self.checksum {
+            crc::put_checksum(checksum, &mut frame, header.len());
+        }
+        frame
+    }
+
+    /**
+     * Counterpart of `framed()` for a header delivered by `header_frame()`:
+     * every frame carries meta and payload only, chunk 0 included.
+     * Chunk boundaries stay the ones of `encode_into`
+     */
+    #[cfg(feature = "alloc")]
+    pub fn payload_chunks(&self) -> impl Iterator<Item = alloc::vec::Vec<u8>> + '_ {
+        (0..self.total_chunks()).map_while(|counter| self.frame_vec(counter, false))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn frame_vec(&self, counter: usize, with_header: bool) -> Option<alloc::vec::Vec<u8>> {
+        let len = self.frame_len(counter, with_header, self.range(counter)?.len());
+        let mut frame = alloc::vec![0; len];
+        self.encode_frame(counter, with_header, &mut frame).ok()?;
+        Some(frame)
+    }
+
+    /**
+     * Wire structure of every frame of `encode_into` with the current options,
+     * e.g. to print in tests and bug reports when a peer rejects frames.
+     * Empty when max_chunk_size can't hold the framing
+     */
+    #[cfg(feature = "alloc")]
+    pub fn describe_layout(&self) -> alloc::vec::Vec<ChunkLayout> {
+        (0..self.total_chunks())
+            .map_while(|counter| {
+                let payload_range = self.range(counter)?;
+                let prefix_len = self.prefix_len(counter, true);
+                let header_bytes = if self.includes_header(counter) {
+                    self.header_len()
+                } else {
+                    0
+                };
+                Some(ChunkLayout {
+                    index: counter,
+                    header_bytes,
+                    meta_bytes: prefix_len - header_bytes,
+                    total: self.frame_len(counter, true, payload_range.len()),
+                    padding_bytes: self.padding(prefix_len + payload_range.len() + self.crc_size),
+                    payload_range,
+                    crc_bytes: self.crc_size,
+                })
+            })
+            .collect()
+    }
+
+    /**
+     * Packs whole wire frames of `encode_into` from the iteration position on
+     * back to back into out until the next one doesn't fit, advancing the
+     * iteration past them, returns the bytes written (0 when not even one fits).
+     * A frame is never split, to find the boundaries the receiver needs
+     * `with_chunk_length()` or frames of the full max_chunk_size
+     */
+    pub fn fill_batch(&mut self, out: &mut [u8]) -> usize {
+        let mut pos = 0;
+        while self.counter < self.back_counter() {
+            let Some(range) = self.range(self.counter) else {
+                break;
+            };
+            let len = self.frame_len(self.counter, true, range.len());
+            if len > out.len() - pos {
+                break;
+            }
+            match self.encode_into(self.counter, &mut out[pos..]) {
+                Ok(written) => pos += written,
+                Err(_) => break,
+            }
+            self.inc_counter();
+        }
+        pos
+    }
+
+    /**
+     * Writes complete wire frame of the chunk into out, returns its length
+     *
+     * [counter: meta][length][session id][header][payload][crc] - first chunk
+     * [counter: meta][length][session id][payload][crc]         - subsequent chunks
+     *
+     * counter    - chunk number, `CounterWidth` wide, `Endianness` ordered,
+     *              top bit marks the final chunk when `with_last_flag()` is set,
+     *              and the terminator of `with_terminator()`
+     * length     - payload length, `CounterWidth` wide, `Endianness` ordered,
+     *              only when `with_chunk_length()` is set
+     * session id - u16, `Endianness` ordered, only when `with_session_id()` is set
+     * header     - `header()`: [version: u8][topic: u8][flags: u8][data length: `LengthWidth`]
+     *              [message crc], first chunk only,
+     *              version byte only when `with_version()` is set,
+     *              flags byte only when `with_header_flags()` is set,
+     *              CRC-32 of data, little-endian, only when `with_message_crc()` is set
+     * payload    - `chunk(Some(counter))`
+     * crc        - CRC-16 over all preceding bytes of the frame, little-endian,
+     *              only when `with_crc()` is set, or the `with_checksum()` one
+     */
+    pub fn encode_into(&self, counter: usize, out: &mut [u8]) -> Result<usize, ChunkError> {
+        self.encode_frame(counter, true, out)
+    }
+
+    /**
+     * Writes the abort frame of `ChunkSession::abort` into out, returns its length:
+     * the reserved abort counter, the highest one meta holds without the
+     * last-chunk flag, followed by chunk length and session id, no header nor
+     * payload. Data chunks never reach that counter, so receivers tell it from
+     * any chunk, drop the message they reassemble and fail with `Aborted`.
+     * With `with_varint()` the counter takes as many bytes as it needs
+     */
+    pub fn encode_abort(&self, out: &mut [u8]) -> Result<usize, ChunkError> {
+        let counter = self.abort_counter();
+        let meta_size = if self.varint {
+            meta::varint_len(counter as u64, self.flagged_counter())
+        } else {
+            self.counter_width.size()
+        };
+        let unpadded = self.frame_overhead_in(meta_size);
+        let len = unpadded + self.padding(unpadded);
+        if out.len() < len {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: len,
+                got: out.len(),
+            });
+        }
+        let pos = if self.varint {
+            meta::put_varint(counter as u64, meta_size, out);
+            meta_size
+        } else {
+            self.counter_width.write(counter, self.endianness, out)?
+        };
+        let mut pos = self.encode_prefix_from(pos, false, 0, out)?;
+        let padding = len - pos - self.crc_size;
+        out[pos..pos + padding].fill(0);
+        pos += padding;
+        if let Some(checksum) = self.checksum {
+            pos = crc::put_checksum(checksum, out, pos);
+        }
+        Ok(pos)
+    }
+
+    fn encode_frame(
+        &self,
+        counter: usize,
+        with_header: bool,
+        out: &mut [u8],
+    ) -> Result<usize, ChunkError> {
+        let data = self.data();
+        self.encode_frame_with(counter, with_header, out, |range, payload| {
+            payload.copy_from_slice(&data[range]);
+            Ok(())
+        })
+    }
+
+    /**
+     * `encode_frame` with the payload at range of data written by fill
+     * into the slice of out it takes, errors of fill are returned as they are
+     */
+    pub(crate) fn encode_frame_with(
+        &self,
+        counter: usize,
+        with_header: bool,
+        out: &mut [u8],
+        fill: impl FnOnce(Range<usize>, &mut [u8]) -> Result<(), ChunkError>,
+    ) -> Result<usize, ChunkError> {
+        let range = self
+            .try_range(counter)?
+            .ok_or(ChunkError::CounterOutOfRange)?;
+        let payload_len = range.len();
+        let len = self.frame_len(counter, with_header, payload_len);
+        if out.len() < len {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: len,
+                got: out.len(),
+            });
+        }
+        let mut pos = self.encode_prefix(counter, with_header, payload_len, out)?;
+        fill(range, &mut out[pos..pos + payload_len])?;
+        pos += payload_len;
+        let padding = len - pos - self.crc_size;
+        out[pos..pos + padding].fill(0);
+        pos += padding;
+        if let Some(checksum) = self.checksum {
+            pos = crc::put_checksum(checksum, out, pos);
+        }
+        trace!("chunk {} encoded, {} bytes", counter, pos);
+        Ok(pos)
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Chunk<'a, &'a T> {
+    /**
+     * Frame of an arbitrary chunk, e.g. to re-emit only the chunks a receiver
+     * asked for, None when counter >= `total_chunks()`.
+     * Wire bytes of the frame come from `encode_into(frame.counter, ..)`.
+     * Payload borrows data for 'a rather than for the lifetime of self
+     */
+    pub fn chunk_at(&self, counter: usize) -> Option<Frame<'a>> {
+        if counter >= self.total_chunks() {
+            return None;
+        }
+        self.frame_in(counter, self.data.as_ref())
+    }
+
+    /**
+     * Payload of every chunk with its range within data, from `start()` to
+     * `end()` clamped to the data length, independent of the iterator position,
+     * e.g. to write chunks into a sparse file
+     */
+    pub fn ranged(&self) -> impl Iterator<Item = (Range<usize>, &'a [u8])> + '_ {
+        let data: &'a [u8] = self.data.as_ref();
+        (0..self.total_chunks())
+            .map_while(|counter| self.range(counter))
+            .map(move |range| (range.clone(), &data[range]))
+    }
+
+    /**
+     * Copy of the chunk iterating only the chunks of chunk_range, e.g. to
+     * resend part of a message. Counters stay absolute, so the frames are
+     * the ones of the whole message, status starts fresh
+     */
+    pub fn slice(&self, chunk_range: Range<usize>) -> Self {
+        let mut slice = self.clone();
+        slice.counter = chunk_range.start;
+        slice.back = Some(chunk_range.end.min(self.total_chunks()));
+        slice.status = ChunkStatus::new();
+        slice
+    }
+
+    /**
+     * Every frame with the delay to wait before sending it to stay at
+     * bytes_per_sec: the time its wire bytes take at that rate, so the delays add
+     * up to `wire_size()` at that rate (rounded down to nanoseconds per frame).
+     * A zero rate means unthrottled, every delay is zero
+     */
+    #[cfg(feature = "std")]
+    pub fn pace(&self, bytes_per_sec: u32) -> impl Iterator<Item = (Frame<'a>, Duration)> + '_ {
+        (0..self.total_chunks())
+            .map_while(|counter| self.chunk_at(counter))
+            .map(move |frame| {
+                let len = self.frame_len(frame.counter, true, frame.payload.len());
+                let delay = match bytes_per_sec {
+                    0 => Duration::ZERO,
+                    rate => Duration::from_nanos(len as u64 * 1_000_000_000 / rate as u64),
+                };
+                (frame, delay)
+            })
+    }
+
+    /**
+     * Frame of the chunk the next `next()` yields, without advancing,
+     * e.g. to check it fits the rest of an outgoing batch
+     */
+    pub fn peek(&self) -> Option<Frame<'a>> {
+        if self.counter >= self.back_counter() {
+            return None;
+        }
+        self.frame_in(self.counter, self.data.as_ref())
+    }
+
+    /**
+     * Frame of the chunk, Ok(None) past the end of data. Fails with
+     * `ChunkSizeTooSmall` or `DataTooLarge` for a misconfigured chunk and with
+     * `CounterOutOfRange` when the offset of counter overflows
+     */
+    pub fn try_chunk(&self, counter: usize) -> Result<Option<Frame<'a>>, ChunkError> {
+        self.try_range(counter)?;
+        Ok(self.frame_in(counter, self.data.as_ref()))
+    }
+
+    /**
+     * Fallible counterpart of `frames()`: a misconfiguration (max_chunk_size
+     * too small, data length not fitting the meta width) is yielded as a single
+     * `Err` item instead of an empty iteration, so `collect::<Result<Vec<_>, _>>()`
+     * surfaces it
+     */
+    pub fn try_chunks(&self) -> impl Iterator<Item = Result<Frame<'a>, ChunkError>> + '_ {
+        let error = self.validate().err();
+        let total = if error.is_some() {
+            0
+        } else {
+            self.total_chunks()
+        };
+        error.map(Err).into_iter().chain(
+            (0..total)
+                .map_while(move |counter| self.chunk_at(counter))
+                .map(Ok),
+        )
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Iterator for Chunk<'a, &'a T> {
+    type Item = (&'a [u8], usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.counter >= self.back_counter() {
+            return None;
+        }
+        match self.range(self.counter) {
+            Some(range) => {
+                let counter = self.counter;
+                self.inc_counter();
+                trace!("chunk {} emitted, {} bytes", counter, range.len());
+                Some((&self.data.as_ref()[range], counter))
+            }
+            None => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back_counter().saturating_sub(self.counter);
+        (remaining, Some(remaining))
+    }
+}
+
+/**
+ * Walks from the last chunk, forward and backward iteration meet in the middle
+ */
+impl<'a, T: AsRef<[u8]> + ?Sized> DoubleEndedIterator for Chunk<'a, &'a T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let back = self.back_counter();
+        if back <= self.counter {
+            return None;
+        }
+        let range = self.range(back - 1)?;
+        self.back = Some(back - 1);
+        Some((&self.data.as_ref()[range], back - 1))
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> ExactSizeIterator for Chunk<'a, &'a T> {}
+
+/**
+ * The counter doesn't move past the end of data, so `next()` keeps returning None
+ * until `reset()` or `seek()`
+ */
+impl<'a, T: AsRef<[u8]> + ?Sized> FusedIterator for Chunk<'a, &'a T> {}
+
+/**
+ * Configuration and position only, the data goes by its length
+ */
+impl<B: AsRef<[u8]>> fmt::Debug for Chunk<'_, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Chunk")
+            .field("topic", &self.topic)
+            .field("max_chunk_size", &self.max_chunk_size)
+            .field("meta_size", &self.meta_size())
+            .field("counter", &self.counter)
+            .field("total_chunks", &self.total_chunks())
+            .field("data_len", &self.data_len())
+            .field("status", &self.status)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    #[test]
+    fn test_chunk() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let mut iter = chunk.into_iter();
+        assert_eq!(
+            iter.next().unwrap().0.len() + core::mem::size_of::<u64>() * 2 + 1,
+            250
+        );
+        assert_eq!(
+            iter.next().unwrap().0.len() + core::mem::size_of::<u64>(),
+            250
+        );
+        assert_eq!(
+            iter.next().unwrap().0.len() + core::mem::size_of::<u64>(),
+            250
+        );
+    }
+
+    #[test]
+    fn test_chunk_with_crc() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        let mut iter = chunk.into_iter();
+        assert_eq!(
+            iter.next().unwrap().0.len() + core::mem::size_of::<u64>() * 2 + 1 + 2,
+            250
+        );
+        assert_eq!(
+            iter.next().unwrap().0.len() + core::mem::size_of::<u64>() + 2,
+            250
+        );
+    }
+
+    struct Xor8;
+
+    impl Checksum for Xor8 {
+        fn width(&self) -> usize {
+            1
+        }
+
+        fn compute(&self, bytes: &[u8]) -> u64 {
+            bytes.iter().fold(0, |sum, byte| sum ^ byte) as u64
+        }
+    }
+
+    #[test]
+    fn test_chunk_with_checksum() {
+        let data = [0x5A; 10];
+        let chunk = Chunk::new(20, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_checksum(&Xor8);
+        assert_eq!(chunk.first_chunk_capacity(), 20 - 2 - 3 - 1);
+        let mut out = [0; 20];
+        let len = chunk.encode_into(0, &mut out).unwrap();
+        assert_eq!(len, 2 + 3 + 10 + 1);
+        let xor = out[..15].iter().fold(0, |sum, byte| sum ^ byte);
+        assert_eq!(out[15], xor);
+        assert!(verify_checksum(&out[..len], &Xor8).is_ok());
+
+        let chunk = Chunk::new(40, 0x10, &data).with_checksum(&Crc32);
+        let mut out = [0; 40];
+        let len = chunk.encode_into(0, &mut out).unwrap();
+        let meta = core::mem::size_of::<u64>();
+        assert_eq!(len, meta + 1 + meta + 10 + 4);
+        assert_eq!(out[len - 4..len], crc32(&out[..len - 4]).to_le_bytes());
+
+        let chunk = Chunk::builder()
+            .max_chunk_size(40)
+            .data(&data)
+            .with_checksum(&Crc16)
+            .build()
+            .unwrap();
+        let mut crc = [0; 40];
+        let crc_len = Chunk::new(40, 0, &data)
+            .with_crc()
+            .encode_into(0, &mut crc)
+            .unwrap();
+        let len = chunk.encode_into(0, &mut out).unwrap();
+        assert_eq!(out[..len], crc[..crc_len]);
+    }
+
+    #[test]
+    fn test_chunk_meta_width() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert_eq!(chunk.header(), [0x10, 0xE8, 0x03]);
+
+        let mut meta = [0; 2];
+        assert!(chunk.encode_meta(u16::MAX as usize + 1, &mut meta).is_err());
+        assert_eq!(chunk.encode_meta(3, &mut meta).unwrap(), 2);
+        assert_eq!(meta, [3, 0]);
+
+        let mut iter = chunk.into_iter();
+        assert_eq!(iter.next().unwrap().0.len() + 2 * 2 + 1, 250);
+        assert_eq!(iter.next().unwrap().0.len() + 2, 250);
+
+        let data = vec![0; u16::MAX as usize + 1];
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert!(chunk.encode_meta(0, &mut meta).is_err());
+    }
+
+    #[test]
+    fn test_getters() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert_eq!(chunk.topic(), 0x10);
+        assert_eq!(chunk.max_chunk_size(), 250);
+        assert_eq!(chunk.meta_size(), 2);
+        assert_eq!(chunk.with_varint().meta_size(), 1);
+    }
+
+    #[test]
+    fn test_debug() {
+        let data = vec![0xAB; 1000];
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        let debug = std::format!("{:?}", chunk);
+        assert_eq!(
+            debug,
+            "Chunk { topic: 16, max_chunk_size: 250, meta_size: 2, counter: 0, \
+             total_chunks: 5, data_len: 1000, status: ChunkStatus { number: None, \
+             session: None, retry: 0 }, .. }"
+        );
+    }
+
+    #[test]
+    fn test_chunk_separate_widths() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_length_width(LengthWidth::U32)
+            .with_counter_width(CounterWidth::U16);
+        assert_eq!(chunk.header(), [0x10, 0xE8, 0x03, 0, 0]);
+        assert_eq!(chunk.first_chunk_capacity(), 250 - 2 - 5);
+        assert_eq!(chunk.chunk_capacity(), 250 - 2);
+
+        let chunk = Chunk::new(9, 0x10, &data)
+            .with_length_width(LengthWidth::U32)
+            .with_counter_width(CounterWidth::U16);
+        assert_eq!(chunk.max_data_len(), 7 * (u16::MAX as usize - 1) + 2);
+        let chunk = Chunk::new(9, 0x10, &data)
+            .with_length_width(LengthWidth::U16)
+            .with_counter_width(CounterWidth::U32);
+        assert_eq!(chunk.max_data_len(), u16::MAX as usize);
+    }
+
+    #[test]
+    fn test_total_chunks() {
+        let first = 250 - core::mem::size_of::<u64>() * 2 - 1;
+        let next = 250 - core::mem::size_of::<u64>();
+        for len in [
+            0,
+            1,
+            first - 1,
+            first,
+            first + 1,
+            first + next,
+            first + next + 1,
+        ] {
+            let data = vec![0; len];
+            let chunk = Chunk::new(250, 0x10, &data);
+            assert_eq!(chunk.total_chunks(), chunk.count(), "data length {}", len);
+        }
+        let data = vec![0; 1000];
+        assert_eq!(Chunk::new(250, 0x10, &data).total_chunks(), 5);
+    }
+
+    #[test]
+    fn test_exact_boundary() {
+        let first = 250 - core::mem::size_of::<u64>() * 2 - 1;
+        let next = 250 - core::mem::size_of::<u64>();
+        for (len, total) in [(first, 1), (first + next, 2), (first + 3 * next, 4)] {
+            let data = vec![0; len];
+            let chunk = Chunk::new(250, 0x10, &data);
+            assert_eq!(chunk.total_chunks(), total, "data length {}", len);
+            let chunks: std::vec::Vec<_> = chunk.collect();
+            assert_eq!(chunks.len(), total);
+            assert!(chunks.iter().all(|(payload, _)| !payload.is_empty()));
+            let chunk = Chunk::new(250, 0x10, &data);
+            assert_eq!(chunk.chunk(Some(total)), None);
+            assert!(chunk.frames().last().unwrap().is_last);
+        }
+    }
+
+    #[test]
+    fn test_wire_size() {
+        let data = vec![0; 1000];
+        let mut out = [0; 250];
+        for chunk in [
+            Chunk::new(250, 0x10, &data),
+            Chunk::new(250, 0x10, &data).with_crc(),
+            Chunk::new(250, 0x10, &data[..0]).with_crc(),
+            Chunk::new(250, 0x10, &data)
+                .with_meta_width(MetaWidth::U16)
+                .with_session_id(1)
+                .with_chunk_length()
+                .with_message_crc()
+                .with_version(1),
+            Chunk::new(250, 0x10, &data).with_repeat_header(),
+        ] {
+            let sum: usize = (0..chunk.total_chunks())
+                .map(|counter| chunk.encode_into(counter, &mut out).unwrap())
+                .sum();
+            assert_eq!(chunk.wire_size(), sum);
+        }
+        assert_eq!(Chunk::new(250, 0x10, &data).wire_size(), 1000 + 5 * 8 + 9);
+        assert_eq!(Chunk::new(5, 0x10, &data).wire_size(), 0);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        assert_eq!(chunk.first_chunk_capacity(), 250 - 8 - 9);
+        assert_eq!(chunk.chunk_capacity(), 250 - 8);
+        let chunk = chunk.with_meta_width(MetaWidth::U16).with_crc();
+        assert_eq!(chunk.first_chunk_capacity(), 250 - 2 - 3 - 2);
+        assert_eq!(chunk.chunk_capacity(), 250 - 2 - 2);
+        let chunk = Chunk::new(10, 0x10, &data);
+        assert_eq!(chunk.first_chunk_capacity(), 0);
+        assert_eq!(chunk.chunk_capacity(), 2);
+        let chunk = Chunk::new(2, 0x10, &data);
+        assert_eq!(chunk.chunk_capacity(), 0);
+    }
+
+    #[test]
+    fn test_chunk_for_offset() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        let first = chunk.first_chunk_capacity();
+        let per_chunk = chunk.chunk_capacity();
+        assert_eq!(first, 245);
+        assert_eq!(chunk.chunk_for_offset(0), Some(0));
+        assert_eq!(chunk.chunk_for_offset(first - 1), Some(0));
+        assert_eq!(chunk.chunk_for_offset(first), Some(1));
+        assert_eq!(chunk.chunk_for_offset(first + per_chunk - 1), Some(1));
+        assert_eq!(chunk.chunk_for_offset(first + per_chunk), Some(2));
+        assert_eq!(chunk.chunk_for_offset(999), Some(4));
+        assert_eq!(chunk.chunk_for_offset(1000), None);
+        for frame in chunk.frames() {
+            let end = frame.data_offset + frame.payload.len();
+            assert_eq!(
+                chunk.chunk_for_offset(frame.data_offset),
+                Some(frame.counter)
+            );
+            assert_eq!(chunk.chunk_for_offset(end - 1), Some(frame.counter));
+        }
+        assert_eq!(Chunk::new(4, 0x10, &data).chunk_for_offset(0), None);
+    }
+
+    #[test]
+    fn test_includes_header() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_crc();
+        assert!(chunk.includes_header(0));
+        assert!(!chunk.includes_header(1));
+        assert!(!chunk.includes_header(4));
+
+        let mut out = [0; 250];
+        assert_eq!(chunk.encode_into(0, &mut out).unwrap(), 250);
+        assert_eq!(out[2..5], *chunk.header());
+        let first = chunk.first_chunk_capacity();
+        assert_eq!(250 - first, 2 + chunk.header().len() + 2);
+        assert_eq!(chunk.encode_into(1, &mut out).unwrap(), 250);
+        assert_eq!(250 - chunk.chunk_capacity(), 2 + 2);
+        assert_eq!(first + chunk.header().len(), chunk.chunk_capacity());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_alignment() {
+        let data: std::vec::Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc().with_alignment(16);
+        assert_eq!(chunk.first_chunk_capacity(), 240 - 8 - 1 - 2 - 9);
+        assert_eq!(chunk.chunk_capacity(), 240 - 8 - 1 - 2);
+        let frames = chunk.to_frames();
+        assert_eq!(frames.len(), 5);
+        for frame in &frames[..4] {
+            assert_eq!(frame.len(), 240);
+            assert_eq!(frame[8], 0);
+        }
+        let last = &frames[4];
+        assert_eq!(last.len(), 112);
+        assert_eq!(last[8], 8);
+        assert_eq!(last[8 + 1 + 93..110], [0; 8]);
+        verify_crc(last).unwrap();
+        assert_eq!(chunk.wire_size(), 4 * 240 + 112);
+        let layout = chunk.describe_layout();
+        assert_eq!(layout[4].padding_bytes, 8);
+        assert_eq!(layout[4].total, 112);
+
+        for alignment in [0, 3, 12, 512] {
+            let chunk = Chunk::new(250, 0x10, &data).with_alignment(alignment);
+            let mut out = [0; 250];
+            assert!(matches!(
+                chunk.encode_into(0, &mut out),
+                Err(ChunkError::InvalidAlignment(got)) if got == alignment
+            ));
+            assert!(matches!(
+                chunk.validate(),
+                Err(ChunkError::InvalidAlignment(got)) if got == alignment
+            ));
+        }
+        let chunk = Chunk::new(512, 0x10, &data).with_alignment(512);
+        assert!(matches!(
+            chunk.validate(),
+            Err(ChunkError::InvalidAlignment(512))
+        ));
+        let chunk = Chunk::new(16, 0x10, &data).with_alignment(16);
+        assert_eq!(chunk.min_chunk_size(), 32);
+        assert!(matches!(
+            chunk.validate(),
+            Err(ChunkError::ChunkSizeTooSmall { min: 32 })
+        ));
+    }
+
+    #[test]
+    fn test_terminator() {
+        let data = vec![0; 1000];
+        let plain = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_terminator()
+            .with_last_flag();
+        assert_eq!(chunk.total_chunks(), plain.total_chunks() + 1);
+        assert_eq!(chunk.wire_size(), plain.wire_size() + 2);
+        let frames: std::vec::Vec<Frame> = chunk.frames().collect();
+        assert!(!frames[4].is_last && frames[5].is_last);
+        assert!(frames[5].payload.is_empty());
+        assert_eq!(frames[5].data_offset, 1000);
+        let mut out = [0; 250];
+        assert_eq!(chunk.encode_into(5, &mut out).unwrap(), 2);
+        assert_eq!(out[..2], [5, 0x80]);
+        assert_eq!(
+            chunk.encode_into(4, &mut out).unwrap(),
+            2 + 1000 - 245 - 3 * 248
+        );
+        assert_eq!(out[..2], [4, 0]);
+        assert_eq!(chunk.count(), 6);
+
+        // the terminator is flagged without `with_last_flag()` as well
+        let chunk = plain.clone().with_terminator();
+        assert_eq!(chunk.encode_into(5, &mut out).unwrap(), 2);
+        assert_eq!(out[..2], [5, 0x80]);
+        chunk.encode_into(4, &mut out).unwrap();
+        assert_eq!(out[..2], [4, 0]);
+        assert_eq!(chunk.max_data_len(), 0xFFFF);
+    }
+
+    #[test]
+    fn test_repeat_header() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_repeat_header();
+        assert!(chunk.includes_header(4));
+        assert_eq!(chunk.chunk_capacity(), chunk.first_chunk_capacity());
+        assert_eq!(chunk.chunk_capacity(), 250 - 2 - 3);
+        assert_eq!(chunk.total_chunks(), 5);
+        let mut out = [0; 250];
+        for counter in 0..chunk.total_chunks() {
+            chunk.encode_into(counter, &mut out).unwrap();
+            assert_eq!(out[2..5], *chunk.header());
+        }
+        assert_eq!(chunk.wire_size(), 1000 + 5 * (2 + 3));
+    }
+
+    #[test]
+    fn test_exact_size() {
+        let data = vec![0; 1000];
+        let mut iter = Chunk::new(250, 0x10, &data);
+        assert_eq!(iter.len(), 5);
+        iter.next();
+        iter.next();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.count(), 3);
+    }
+
+    #[test]
+    fn test_double_ended() {
+        let data = vec![0; 1000];
+        let mut iter = Chunk::new(250, 0x10, &data);
+        let mut seen = std::vec::Vec::new();
+        while let Some((_, counter)) = iter.next() {
+            seen.push(counter);
+            assert_eq!(iter.len(), 5 - seen.len());
+            match iter.next_back() {
+                Some((_, counter)) => seen.push(counter),
+                None => break,
+            }
+        }
+        assert_eq!(seen, [0, 4, 1, 3, 2]);
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.next(), None);
+
+        let rev: std::vec::Vec<_> = Chunk::new(250, 0x10, &data).rev().map(|(_, c)| c).collect();
+        assert_eq!(rev, [4, 3, 2, 1, 0]);
+        iter.reset();
+        assert_eq!(iter.count(), 5);
+    }
+
+    #[test]
+    fn test_fused() {
+        let data = vec![0; 1000];
+        let mut iter = Chunk::new(250, 0x10, &data);
+        assert_eq!(iter.by_ref().count(), 5);
+        for _ in 0..3 {
+            assert_eq!(iter.next(), None);
+        }
+        assert_eq!(iter.counter(), 5);
+        assert_eq!(iter.len(), 0);
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let data = [1, 2, 3, 4, 5];
+        let chunk = Chunk::new(9, 0x10, &data).with_meta_width(MetaWidth::U16);
+        let mut out = [0; 9];
+        assert_eq!(chunk.encode_into(0, &mut out).unwrap(), 9);
+        assert_eq!(out[..9], [0, 0, 0x10, 5, 0, 1, 2, 3, 4]);
+        assert_eq!(chunk.encode_into(1, &mut out).unwrap(), 3);
+        assert_eq!(out[..3], [1, 0, 5]);
+        assert!(matches!(
+            chunk.encode_into(2, &mut out),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+        assert!(matches!(
+            chunk.encode_into(0, &mut out[..8]),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_to_frames() {
+        let data = [1, 2, 3, 4, 5];
+        let chunk = Chunk::new(9, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert_eq!(
+            chunk.to_frames(),
+            [vec![0, 0, 0x10, 5, 0, 1, 2, 3, 4], vec![1, 0, 5]]
+        );
+        assert!(Chunk::new(4, 0x10, &data).to_frames().is_empty());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_framed() {
+        let data: alloc::vec::Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        let mut framed = chunk.framed();
+        let first = framed.next().unwrap();
+        assert_eq!(first.len(), 250);
+        assert_eq!(first.capacity(), 250);
+        assert_eq!(framed.count(), 4);
+
+        let last = chunk.framed().last().unwrap();
+        let mut out = [0; 250];
+        let len = chunk.encode_into(4, &mut out).unwrap();
+        assert_eq!(last, out[..len]);
+        assert_eq!(last.capacity(), len);
+        assert_eq!(chunk.framed().take(2).count(), 2);
+        assert_eq!(
+            chunk.framed().collect::<alloc::vec::Vec<_>>(),
+            chunk.to_frames()
+        );
+    }
+
+    #[test]
+    fn test_error_display() {
+        use std::string::ToString;
+
+        let err = ChunkError::InvalidMetaSize {
+            expected: 9,
+            got: 4,
+        };
+        assert_eq!(
+            err.to_string(),
+            "invalid meta size, expected 9 bytes, got 4"
+        );
+        let err: std::boxed::Box<dyn core::error::Error> = std::boxed::Box::new(err);
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_empty_data() {
+        let data = [];
+        let chunk = Chunk::new(250, 0x10, &data);
+        assert_eq!(chunk.total_chunks(), 1);
+        assert_eq!(chunk.chunk(Some(0)), Some((&[][..], 0)));
+        assert_eq!(chunk.chunk(Some(1)), None);
+
+        let mut out = [0; 250];
+        let len = chunk.encode_into(0, &mut out).unwrap();
+        assert_eq!(len, core::mem::size_of::<u64>() + chunk.header().len());
+
+        let mut iter = chunk.into_iter();
+        assert_eq!(iter.next(), Some((&[][..], 0)));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_single_chunk() {
+        let data: std::vec::Vec<u8> = (0..231).map(|i| i as u8).collect();
+        for len in [1, 100, 231] {
+            let chunk = Chunk::new(250, 0x10, &data[..len]).with_crc();
+            assert_eq!(chunk.first_chunk_capacity(), 231);
+            assert_eq!(chunk.total_chunks(), 1);
+
+            let frame = chunk.frames().next().unwrap();
+            assert!(frame.is_first && frame.is_last);
+            assert_eq!(frame.payload, &data[..len]);
+            assert_eq!(chunk.chunk_at(1), None);
+
+            let mut out = [0; 250];
+            let frame_len = chunk.encode_into(0, &mut out).unwrap();
+            assert_eq!(frame_len, 8 + chunk.header().len() + len + 2);
+            let decoded = Frame::try_from((verify_crc(&out[..frame_len]).unwrap(), 250)).unwrap();
+            assert!(decoded.is_first && decoded.is_last);
+            assert_eq!(decoded.payload, &data[..len]);
+
+            let mut iter = chunk.into_iter();
+            assert_eq!(iter.next(), Some((&data[..len], 0)));
+            assert_eq!(iter.next(), None);
+            assert_eq!(iter.next(), None);
+        }
+        let data = [0; 232];
+        assert_eq!(Chunk::new(250, 0x10, &data).with_crc().total_chunks(), 2);
+    }
+
+    #[test]
+    fn test_header_len() {
+        let data = [0; 1000];
+        for meta_width in [MetaWidth::U16, MetaWidth::U32, MetaWidth::U64] {
+            let chunks = [
+                Chunk::new(250, 0x10, &data),
+                Chunk::new(250, 0x10, &data).with_crc().with_session_id(1),
+                Chunk::new(250, 0x10, &data).with_chunk_count(),
+                Chunk::new(250, 0x10, &data)
+                    .with_message_crc()
+                    .with_last_flag(),
+                Chunk::new(250, 0x10, &data)
+                    .with_chunk_count()
+                    .with_message_crc()
+                    .with_terminator(),
+            ];
+            for chunk in chunks {
+                let chunk = chunk.with_meta_width(meta_width).with_header_flags();
+                assert_eq!(header_len(meta_width, chunk.flags()), chunk.header().len());
+            }
+        }
+        let flags = HeaderFlags::from_bits(FLAG_CHUNK_COUNT).unwrap();
+        assert_eq!(header_len(MetaWidth::U16, flags), 6);
+    }
+
+    #[test]
+    fn test_default_wire_format() {
+        // fixed bytes, the same frames on every target whatever its usize
+        let data = b"hello world, chunked";
+        let chunk = Chunk::new(24, 0x10, data);
+        let mut frame = [0; 24];
+        let len = chunk.encode_into(0, &mut frame).unwrap();
+        assert_eq!(
+            frame[..len],
+            [
+                0, 0, 0, 0, 0, 0, 0, 0, 0x10, 20, 0, 0, 0, 0, 0, 0, 0, b'h', b'e', b'l', b'l',
+                b'o', b' ', b'w'
+            ]
+        );
+        let len = chunk.encode_into(1, &mut frame).unwrap();
+        assert_eq!(
+            frame[..len],
+            [
+                1, 0, 0, 0, 0, 0, 0, 0, b'o', b'r', b'l', b'd', b',', b' ', b'c', b'h', b'u', b'n',
+                b'k', b'e', b'd'
+            ]
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_find_header() {
+        let data = [7; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_header_flags()
+            .with_crc()
+            .with_chunk_length();
+        let frames = chunk.to_frames();
+        let mut stream = frames[3][100..].to_vec();
+        let start = stream.len();
+        stream.extend_from_slice(&frames[0]);
+        assert_eq!(find_header(&stream, chunk.flags()), Some(start));
+        assert_eq!(find_header(&frames[0], chunk.flags()), Some(0));
+
+        // a corrupt first frame fails the CRC, other flags fail the flags byte
+        let mut corrupt = stream.clone();
+        corrupt[start + 100] ^= 1;
+        assert_eq!(find_header(&corrupt, chunk.flags()), None);
+        let flags = HeaderFlags::from_bits(FLAG_CRC).unwrap();
+        assert_eq!(find_header(&stream, flags), None);
+        assert_eq!(find_header(&frames[1], chunk.flags()), None);
+    }
+
+    #[test]
+    fn test_overhead() {
+        const _: () = assert!(250 > BASE_HEADER_LEN + MetaWidth::U64.size());
+        let data = [0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        assert_eq!(chunk.overhead(), BASE_HEADER_LEN + chunk.meta_size());
+        assert_eq!(chunk.overhead(), chunk.header().len() + chunk.meta_size());
+        let chunks = [
+            chunk.clone().with_crc().with_session_id(1),
+            chunk
+                .clone()
+                .with_meta_width(MetaWidth::U16)
+                .with_chunk_count(),
+            chunk.clone().with_varint().with_message_crc(),
+        ];
+        for chunk in chunks {
+            assert_eq!(chunk.overhead() + chunk.first_chunk_capacity(), 250);
+        }
+    }
+
+    #[test]
+    fn test_counter_overflow() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        assert_eq!(chunk.chunk(Some(usize::MAX / 2)), None);
+        assert_eq!(chunk.chunk(Some(usize::MAX)), None);
+
+        let chunk = Chunk::new(usize::MAX / 2, 0x10, &data);
+        assert_eq!(chunk.chunk(Some(0)).unwrap().0.len(), 1000);
+        assert_eq!(chunk.chunk(Some(usize::MAX / 2)), None);
+    }
+
+    #[test]
+    fn test_chunk_size_too_small() {
+        let data = vec![0; 1000];
+        let min = 1 + core::mem::size_of::<u64>() * 2 + 1;
+        assert!(matches!(
+            Chunk::try_new(10, 0, &data),
+            Err(ChunkError::ChunkSizeTooSmall { min: m }) if m == min
+        ));
+        assert!(Chunk::try_new(min, 0, &data).is_ok());
+
+        let chunk = Chunk::new(10, 0, &data);
+        assert_eq!(chunk.total_chunks(), 0);
+        assert_eq!(chunk.count(), 0);
+    }
+
+    #[test]
+    fn test_validate_frame() {
+        let data = [0; 100];
+        let chunk = Chunk::new(40, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_last_flag();
+        let mut frame = [0; 41];
+        let len = chunk.encode_into(2, &mut frame).unwrap();
+        assert!(chunk.validate_frame(&frame[..len], Some(2)).is_ok());
+        assert!(chunk.validate_frame(&frame[..len], None).is_ok());
+        assert!(matches!(
+            chunk.validate_frame(&frame[..len], Some(1)),
+            Err(ChunkError::UnexpectedChunkNumber {
+                expected: Some(1),
+                got: 2
+            })
+        ));
+        assert!(matches!(
+            chunk.validate_frame(&frame[..1], None),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+        assert!(matches!(
+            chunk.validate_frame(&frame, None),
+            Err(ChunkError::FrameTooLarge { max: 40, got: 41 })
+        ));
+    }
+
+    #[test]
+    fn test_set_max_chunk_size() {
+        let data = vec![0; 1000];
+        let mut chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        chunk.next();
+        chunk.status.to_send(0);
+        assert_eq!(chunk.total_chunks(), 5);
+
+        assert!(matches!(
+            chunk.set_max_chunk_size(5),
+            Err(ChunkError::ChunkSizeTooSmall { min: 6 })
+        ));
+        assert_eq!(chunk.counter(), 1);
+        assert_eq!(chunk.total_chunks(), 5);
+
+        chunk.set_max_chunk_size(500).unwrap();
+        assert_eq!(chunk.counter(), 0);
+        assert_eq!(chunk.status.number, None);
+        assert_eq!(chunk.total_chunks(), 3);
+        assert_eq!(chunk.next().unwrap().0.len(), 500 - 2 - 3);
+    }
+
+    #[test]
+    fn test_try_chunk() {
+        let data = [1, 2, 3, 4, 5];
+        let chunk = Chunk::new(9, 0x10, &data).with_meta_width(MetaWidth::U16);
+        let frame = chunk.try_chunk(1).unwrap().unwrap();
+        assert_eq!(frame.payload, [5]);
+        assert!(frame.is_last);
+        assert!(chunk.try_chunk(2).unwrap().is_none());
+        assert!(matches!(
+            chunk.try_chunk(usize::MAX),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+        assert!(chunk.chunk(Some(usize::MAX)).is_none());
+
+        let chunk = Chunk::new(4, 0x10, &data);
+        assert!(matches!(
+            chunk.try_chunk(0),
+            Err(ChunkError::ChunkSizeTooSmall { .. })
+        ));
+        assert!(chunk.chunk(Some(0)).is_none());
+    }
+
+    #[test]
+    fn test_set_topic() {
+        let data = [0; 1000];
+        let mut chunk = Chunk::new(250, 0x10, &data);
+        chunk.next();
+        chunk.status.to_send(0);
+        chunk.set_topic(0x20);
+        assert_eq!(chunk.counter(), 0);
+        assert_eq!(chunk.status.number, None);
+        assert_eq!(chunk.header(), Chunk::new(250, 0x20, &data).header());
+        let (mut frame, mut expected) = ([0; 250], [0; 250]);
+        chunk.encode_into(0, &mut frame).unwrap();
+        Chunk::new(250, 0x20, &data)
+            .encode_into(0, &mut expected)
+            .unwrap();
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn test_reset_seek() {
+        let data = vec![0; 1000];
+        let mut chunk = Chunk::new(250, 0x10, &data);
+        let first = chunk.next();
+        chunk.status.to_send(0);
+        assert_eq!(chunk.by_ref().count(), 4);
+        chunk.reset();
+        assert!(chunk.status.number.is_none());
+        assert_eq!(chunk.next(), first);
+
+        chunk.seek(3);
+        assert_eq!(chunk.next().unwrap().1, 3);
+        assert_eq!(chunk.len(), 1);
+    }
+
+    #[test]
+    fn test_frames() {
+        let data = vec![0; 1000];
+        let mut chunk = Chunk::new(250, 0x10, &data);
+        chunk.next();
+        let frames: std::vec::Vec<Frame> = chunk.frames().collect();
+        assert_eq!(frames.len(), 5);
+        assert!(frames[0].is_first && !frames[0].is_last);
+        assert!(frames[1..4].iter().all(|f| !f.is_first && !f.is_last));
+        assert!(frames[4].is_last);
+        assert_eq!(frames[4].counter, 4);
+        assert_eq!(frames[4].topic, 0x10);
+        assert_eq!(frames.iter().map(|f| f.payload.len()).sum::<usize>(), 1000);
+        assert_eq!(chunk.counter(), 1);
+    }
+
+    #[test]
+    fn test_try_chunks() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let frames = chunk.try_chunks().collect::<Result<std::vec::Vec<_>, _>>();
+        assert_eq!(
+            frames.unwrap(),
+            chunk.frames().collect::<std::vec::Vec<_>>()
+        );
+
+        let chunk = Chunk::new(10, 0x10, &data);
+        let mut frames = chunk.try_chunks();
+        assert!(matches!(
+            frames.next(),
+            Some(Err(ChunkError::ChunkSizeTooSmall { min: 18 }))
+        ));
+        assert!(frames.next().is_none());
+
+        let data = vec![0; 0x10000];
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert!(matches!(
+            chunk.try_chunks().collect::<Result<std::vec::Vec<_>, _>>(),
+            Err(ChunkError::DataTooLarge { max: 0xFFFF })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_at() {
+        let data = vec![0; 1000];
+        let mut chunk = Chunk::new(250, 0x10, &data);
+        let frames: std::vec::Vec<Frame> = chunk.frames().collect();
+        let third = chunk.chunk_at(3);
+        assert_eq!(third, Some(frames[3]));
+        assert_eq!(chunk.chunk_at(0), Some(frames[0]));
+        assert_eq!(chunk.chunk_at(5), None);
+        assert_eq!(chunk.counter(), 0);
+
+        let mut out = [0; 250];
+        let len = chunk.encode_into(3, &mut out).unwrap();
+        assert_eq!(out[8..len], *chunk.chunk_at(3).unwrap().payload);
+        chunk.next();
+        assert_eq!(chunk.chunk_at(3), third);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_describe_layout() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_crc()
+            .with_session_id(7);
+        let layout = chunk.describe_layout();
+        assert_eq!(layout.len(), chunk.total_chunks());
+        assert_eq!(
+            layout[0],
+            ChunkLayout {
+                index: 0,
+                header_bytes: 3,
+                meta_bytes: 4,
+                payload_range: 0..241,
+                padding_bytes: 0,
+                crc_bytes: 2,
+                total: 250,
+            }
+        );
+        let mut out = [0; 250];
+        for (counter, frame) in layout.iter().enumerate() {
+            assert_eq!(frame.index, counter);
+            assert_eq!(chunk.encode_into(counter, &mut out).unwrap(), frame.total);
+            assert_eq!(
+                frame.meta_bytes
+                    + frame.header_bytes
+                    + frame.payload_range.len()
+                    + frame.padding_bytes
+                    + frame.crc_bytes,
+                frame.total
+            );
+        }
+        assert_eq!(layout[4].payload_range.end, data.len());
+        assert!(Chunk::new(5, 0x10, &data).describe_layout().is_empty());
+    }
+
+    #[test]
+    fn test_clone_and_slice() {
+        let data: std::vec::Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut chunk = Chunk::new(250, 0x10, &data).with_crc();
+        chunk.next();
+        let clone = chunk.clone();
+        assert_eq!(clone.counter(), 1);
+        assert!(clone.eq(chunk.by_ref()));
+
+        let slice = chunk.slice(1..3);
+        let counters: std::vec::Vec<usize> = slice.clone().map(|(_, counter)| counter).collect();
+        assert_eq!(counters, [1, 2]);
+        let mut expected = [0; 250];
+        let mut got = [0; 250];
+        for counter in counters {
+            let len = chunk.encode_into(counter, &mut expected).unwrap();
+            assert_eq!(slice.encode_into(counter, &mut got).unwrap(), len);
+            assert_eq!(got[..len], expected[..len]);
+        }
+        assert_eq!(chunk.slice(3..10).count(), 2);
+        assert_eq!(chunk.slice(7..10).count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_pace() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        let paced: std::vec::Vec<(Frame, Duration)> = chunk.pace(1000).collect();
+        assert_eq!(paced.len(), chunk.total_chunks());
+        assert_eq!(paced[0].1, Duration::from_millis(250));
+        let total: Duration = paced.iter().map(|(_, delay)| *delay).sum();
+        assert_eq!(total, Duration::from_millis(chunk.wire_size() as u64));
+        for ((frame, _), expected) in paced.iter().zip(chunk.frames()) {
+            assert_eq!(*frame, expected);
+        }
+        assert!(chunk.pace(0).all(|(_, delay)| delay.is_zero()));
+    }
+
+    #[test]
+    fn test_fill_batch() {
+        let data: std::vec::Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut chunk = Chunk::new(250, 0x10, &data).with_crc();
+        let mut batch = [0; 625];
+        assert_eq!(chunk.fill_batch(&mut batch), 500);
+        assert_eq!(chunk.counter(), 2);
+        let mut frame = [0; 250];
+        for counter in 0..2 {
+            chunk.encode_into(counter, &mut frame).unwrap();
+            assert_eq!(batch[counter * 250..(counter + 1) * 250], frame);
+        }
+        assert_eq!(chunk.fill_batch(&mut batch[..249]), 0);
+        assert_eq!(chunk.counter(), 2);
+        let last = 1000 - chunk.first_chunk_capacity() - 3 * chunk.chunk_capacity();
+        assert_eq!(chunk.fill_batch(&mut batch), 500 + last + 8 + 2);
+        assert_eq!(chunk.fill_batch(&mut batch), 0);
+        assert_eq!(chunk.next(), None);
+    }
+
+    #[test]
+    fn test_ranged() {
+        let data: std::vec::Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data);
+        let mut end = 0;
+        for ((range, payload), (expected, _)) in chunk.ranged().zip(chunk.clone()) {
+            assert_eq!(range.start, end);
+            assert!(range.start < range.end);
+            assert_eq!(payload, expected);
+            assert_eq!(&data[range.clone()], payload);
+            end = range.end;
+        }
+        assert_eq!(end, data.len());
+        assert_eq!(chunk.ranged().count(), chunk.total_chunks());
+        assert_eq!(chunk.ranged().last().unwrap().0, 959..1000);
+    }
+
+    #[test]
+    fn test_peek() {
+        let data = vec![0; 1000];
+        let mut chunk = Chunk::new(250, 0x10, &data);
+        for _ in 0..chunk.total_chunks() {
+            let peeked = chunk.peek().unwrap();
+            assert_eq!(chunk.peek(), Some(peeked));
+            let (payload, counter) = chunk.next().unwrap();
+            assert_eq!((peeked.payload, peeked.counter), (payload, counter));
+        }
+        assert_eq!(chunk.peek(), None);
+
+        chunk.reset();
+        chunk.next_back();
+        chunk.seek(4);
+        assert_eq!(chunk.peek(), None);
+    }
+
+    #[test]
+    fn test_resume_from() {
+        let data = [0; 1000];
+        let mut chunk = Chunk::new(250, 0x10, &data);
+        chunk.status.to_send(2);
+        chunk.resume_from(3).unwrap();
+        let (payload, counter) = chunk.next().unwrap();
+        assert_eq!(counter, 3);
+        assert_eq!(payload.len(), 242);
+        assert_eq!(chunk.status.number, Some(2));
+        assert_eq!(chunk.count(), 1);
+
+        // a counter saved for longer data
+        let mut chunk = Chunk::new(250, 0x10, &data[..500]);
+        assert!(matches!(
+            chunk.resume_from(3),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+        assert_eq!(chunk.counter(), 0);
+        chunk.resume_from(2).unwrap();
+        assert_eq!(chunk.next().unwrap().1, 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_status_serde() {
+        let mut status = ChunkStatus::new();
+        status.to_send(3);
+        status.increase_retry().unwrap();
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(
+            json,
+            r#"{"number":3,"session":{"status":"Sent"},"retry":1}"#
+        );
+        let status: ChunkStatus = serde_json::from_str(&json).unwrap();
+        assert_eq!(status.number, Some(3));
+        assert!(matches!(status.session, Some(ChunkSessionStatus::Sent)));
+        let status: ChunkStatus =
+            serde_json::from_str(r#"{"number":3,"session":{"status":"Sended"},"retry":1}"#)
+                .unwrap();
+        assert_eq!(status.session, Some(ChunkSessionStatus::Sent));
+        assert_eq!(status.retry, 1);
+
+        let err = ChunkError::InvalidMetaSize {
+            expected: 9,
+            got: 4,
+        };
+        let json = serde_json::to_string(&err).unwrap();
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            ChunkError::InvalidMetaSize {
+                expected: 9,
+                got: 4
+            }
+        ));
+    }
+
+    #[test]
+    fn test_owned_data() {
+        fn build(len: usize) -> Chunk<'static, std::vec::Vec<u8>> {
+            Chunk::from_data(250, 0x10, vec![0; len])
+        }
+        let chunk = build(1000);
+        assert_eq!(chunk.total_chunks(), 5);
+        assert_eq!(chunk.frames().count(), 5);
+        assert_eq!(chunk.chunk(Some(4)).unwrap().0.len(), 41);
+
+        let boxed: std::boxed::Box<[u8]> = vec![0; 1000].into_boxed_slice();
+        let chunk = Chunk::from_data(250, 0x10, boxed);
+        let mut out = [0; 250];
+        assert_eq!(chunk.encode_into(1, &mut out).unwrap(), 250);
+        assert_eq!(
+            chunk.header(),
+            Chunk::new(250, 0x10, &[0; 1000][..]).header()
+        );
+    }
+
+    #[test]
+    fn test_header() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let header = chunk.header();
+
+        assert_eq!(header.len(), core::mem::size_of::<u64>() + 1);
+        assert_eq!(header, [0x10, 0xE8, 0x03, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let header = chunk.header();
+        assert_eq!(Chunk::parse_header(&header).unwrap(), (0x10, 1000));
+        assert!(matches!(
+            Chunk::parse_header(&header[..header.len() - 1]),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+
+        let chunk = chunk.with_meta_width(MetaWidth::U16);
+        let header = chunk.header();
+        assert_eq!(
+            Header::parse(&header, MetaWidth::U16, Endianness::Little).unwrap(),
+            (0x10, 1000)
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[repr(u8)]
+    enum Kind {
+        Ping = 1,
+        Data = 2,
+    }
+
+    impl From<Kind> for u8 {
+        fn from(kind: Kind) -> u8 {
+            kind as u8
+        }
+    }
+
+    impl TryFrom<u8> for Kind {
+        type Error = ();
+
+        fn try_from(value: u8) -> Result<Self, ()> {
+            match value {
+                1 => Ok(Kind::Ping),
+                2 => Ok(Kind::Data),
+                _ => Err(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_topic_enum() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, Kind::Data, &data);
+        let header = chunk.header();
+        assert_eq!(header[0], 2);
+        assert_eq!(
+            Chunk::parse_header_as::<Kind>(&header).unwrap(),
+            (Kind::Data, 1000)
+        );
+        let header = Chunk::new(250, Kind::Ping, &data).header();
+        assert_eq!(Chunk::parse_header_as(&header).unwrap(), (Kind::Ping, 1000));
+        let header = Chunk::new(250, 3, &data).header();
+        assert!(matches!(
+            Chunk::parse_header_as::<Kind>(&header),
+            Err(ChunkError::InvalidTopic(3))
+        ));
+    }
+
+    #[test]
+    fn test_last_flag() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_last_flag();
+        let mut out = [0; 250];
+        chunk.encode_into(0, &mut out).unwrap();
+        assert_eq!(out[..2], [0, 0]);
+        chunk.encode_into(3, &mut out).unwrap();
+        assert_eq!(out[..2], [3, 0]);
+        chunk.encode_into(4, &mut out).unwrap();
+        assert_eq!(out[..2], [4, 0x80]);
+
+        let chunk = chunk.with_endianness(Endianness::Big);
+        chunk.encode_into(4, &mut out).unwrap();
+        assert_eq!(out[..2], [0x80, 4]);
+        assert!(matches!(
+            chunk.encode_meta(0x8000, &mut out),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn test_session_id() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_session_id(0x0102);
+        assert_eq!(chunk.min_chunk_size(), 3 + 2 + 2 + 1);
+        assert_eq!(chunk.chunk_capacity(), 250 - 2 - 2);
+        let mut out = [0; 250];
+        assert_eq!(chunk.encode_into(1, &mut out).unwrap(), 250);
+        assert_eq!(out[..4], [1, 0, 0x02, 0x01]);
+        assert_eq!(chunk.frames().nth(2).unwrap().session_id, Some(0x0102));
+
+        let chunk = chunk.with_endianness(Endianness::Big);
+        chunk.encode_into(0, &mut out).unwrap();
+        assert_eq!(out[..7], [0, 0, 0x01, 0x02, 0x10, 0x03, 0xE8]);
+    }
+
+    #[test]
+    fn test_chunk_length() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_chunk_length();
+        assert_eq!(chunk.chunk_capacity(), 250 - 2 - 2);
+        let mut out = [0; 250];
+        chunk.encode_into(0, &mut out).unwrap();
+        assert_eq!(out[..4], [0, 0, 243, 0]);
+        let last = chunk.total_chunks() - 1;
+        let len = chunk.encode_into(last, &mut out).unwrap();
+        assert_eq!(out[2..4], ((len - 4) as u16).to_le_bytes());
+    }
+
+    #[test]
+    fn test_header_version() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert_eq!(chunk.header().len(), 3);
+        let chunk = chunk.with_version(2);
+        let header = chunk.header();
+        assert_eq!(header, [2, 0x10, 0xE8, 0x03]);
+        assert_eq!(chunk.min_chunk_size(), 4 + 2 + 1);
+        assert_eq!(
+            Header::parse_versioned(&header, 2, MetaWidth::U16, Endianness::Little).unwrap(),
+            (0x10, 1000)
+        );
+        assert!(matches!(
+            Header::parse_versioned(&header, 1, MetaWidth::U16, Endianness::Little),
+            Err(ChunkError::UnsupportedVersion(2))
+        ));
+
+        let header = Chunk::new(250, 0x10, &data).with_version(1).header();
+        assert_eq!(header.len(), 2 + core::mem::size_of::<u64>());
+        assert_eq!(
+            Chunk::parse_versioned_header(&header, 1).unwrap(),
+            (0x10, 1000)
+        );
+    }
+
+    #[test]
+    fn test_header_flags() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_meta_width(MetaWidth::U16)
+            .with_crc()
+            .with_header_flags();
+        assert_eq!(chunk.flags().bits(), FLAG_CRC);
+        let header = chunk.header();
+        assert_eq!(header, [0x10, FLAG_CRC, 0xE8, 0x03]);
+        assert_eq!(chunk.min_chunk_size(), 4 + 2 + 2 + 1);
+        let (topic, flags, len) =
+            Header::parse_flagged(&header, MetaWidth::U16, Endianness::Little).unwrap();
+        assert_eq!((topic, len), (0x10, 1000));
+        assert!(flags.contains(FLAG_CRC));
+        assert!(!flags.contains(FLAG_COMPRESSED));
+
+        let chunk = chunk
+            .with_version(1)
+            .with_last_flag()
+            .with_session_id(7)
+            .with_message_crc()
+            .with_chunk_length();
+        assert_eq!(
+            chunk.flags().bits(),
+            FLAG_CRC | FLAG_HAS_SESSION_ID | FLAG_LAST_CHUNK | FLAG_MESSAGE_CRC | FLAG_CHUNK_LENGTH
+        );
+        assert_eq!(chunk.header()[..2], [1, 0x10]);
+
+        assert!(matches!(
+            HeaderFlags::from_bits(0xFF),
+            Err(ChunkError::UnknownFlags(0x80))
+        ));
+        let header = Chunk::new(250, 0x10, &data)
+            .with_terminator()
+            .with_header_flags()
+            .header();
+        assert_eq!(header[1], 0);
+        let header = Chunk::new(250, 0x10, &data).with_header_flags().header();
+        assert_eq!(header.len(), 2 + core::mem::size_of::<u64>());
+        let (_, flags, len) = Chunk::parse_flagged_header(&header).unwrap();
+        assert_eq!((flags, len), (HeaderFlags::default(), 1000));
+    }
+
+    #[test]
+    fn test_header_big_endian() {
+        let data = vec![0; 1000];
+        let chunk = Chunk::new(250, 0x10, &data);
+        let little = chunk.header();
+        let chunk = chunk.with_endianness(Endianness::Big);
+        let big = chunk.header();
+
+        assert_eq!(big[0], little[0]);
+        assert!(big[1..].iter().eq(little[1..].iter().rev()));
+        assert_eq!(big, [0x10, 0, 0, 0, 0, 0, 0, 0x03, 0xE8]);
+        assert_eq!(
+            Header::parse(&big, MetaWidth::default(), Endianness::Big).unwrap(),
+            (0x10, 1000)
+        );
+
+        let mut meta = [0; 8];
+        let len = chunk.encode_meta(0x0102, &mut meta).unwrap();
+        assert_eq!(meta[len - 2..len], [0x01, 0x02]);
+    }
+
+    #[test]
+    fn test_remaining_retries() {
+        let mut status = ChunkStatus::new();
+        status.to_send(0);
+        assert_eq!(status.remaining_retries(5), 5);
+        for _ in 0..5 {
+            status.increase_retry().unwrap();
+        }
+        assert_eq!(status.remaining_retries(5), 0);
+        status.increase_retry().unwrap();
+        assert_eq!(status.remaining_retries(5), 0);
+        assert_eq!(status.remaining_retries(u8::MAX), u8::MAX - 6);
+        status.reset_retry();
+        assert_eq!(status.retry, 0);
+        assert_eq!(status.number, Some(0));
+    }
+
+    #[test]
+    fn test_data_too_large() {
+        let data = vec![0; 0x10000];
+        let build = |len| {
+            Chunk::builder()
+                .max_chunk_size(250)
+                .topic(0x10)
+                .data(&data[..len])
+                .meta_width(MetaWidth::U16)
+                .build()
+        };
+        assert!(build(0xFFFF).is_ok());
+        assert!(matches!(
+            build(0x10000),
+            Err(ChunkError::DataTooLarge { max: 0xFFFF })
+        ));
+
+        let chunk = Chunk::new(250, 0x10, &data[..0x8000])
+            .with_meta_width(MetaWidth::U16)
+            .with_last_flag();
+        assert_eq!(chunk.max_data_len(), 0xFFFF);
+        assert!(chunk.validate().is_ok());
+
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U32);
+        assert_eq!(chunk.max_data_len() as u64, u32::MAX as u64);
+        assert!(chunk.validate().is_ok());
+
+        let chunk = Chunk::new(250, 0x10, &data).with_meta_width(MetaWidth::U64);
+        assert_eq!(chunk.max_data_len(), usize::MAX);
+
+        // too small to describe a chunk, only the length field limits
+        let chunk = Chunk::new(1, 0x10, &data).with_meta_width(MetaWidth::U16);
+        assert_eq!(chunk.max_data_len(), 0xFFFF);
+    }
+
+    #[test]
+    fn test_status_byte() {
+        let mut status = ChunkStatus::new();
+        assert_eq!(u8::from(&status), 0);
+        status.to_send(3);
+        status.increase_retry().unwrap();
+        assert_eq!(u8::from(&status), 1);
+        status.retry = u8::MAX;
+        assert_eq!(u8::from(&status), 0x81);
+        status.to_received(3).unwrap();
+        assert_eq!(u8::from(&status), 2);
+
+        for byte in [0, 1, 2, 3, 0x80, 0x81, 0x82] {
+            assert_eq!(u8::from(&ChunkStatus::try_from(byte).unwrap()), byte);
+        }
+        let parsed = ChunkStatus::try_from(0x81).unwrap();
+        assert_eq!(parsed.session, Some(ChunkSessionStatus::Sent));
+        assert_eq!(parsed.retry, u8::MAX);
+        assert_eq!(parsed.number, None);
+        for byte in [4, 0x7F, 0x84, 0xFF] {
+            assert!(matches!(
+                ChunkStatus::try_from(byte),
+                Err(ChunkError::InvalidStatus(got)) if got == byte
+            ));
+        }
+        assert!(matches!(
+            ChunkSessionStatus::try_from(0),
+            Err(ChunkError::InvalidStatus(0))
+        ));
+        #[allow(deprecated)]
+        let sended = ChunkSessionStatus::Sended;
+        assert_eq!(sended, ChunkSessionStatus::Sent);
+    }
+
+    #[test]
+    fn test_status_received() {
+        let mut status = ChunkStatus::new();
+        assert!(matches!(
+            status.to_received(0),
+            Err(ChunkError::UnexpectedChunkNumber {
+                expected: None,
+                got: 0
+            })
+        ));
+        status.to_send(1);
+        assert!(matches!(
+            status.to_received(2),
+            Err(ChunkError::UnexpectedChunkNumber {
+                expected: Some(1),
+                got: 2
+            })
+        ));
+        assert!(status.to_received(1).is_ok());
+        assert!(matches!(status.session, Some(ChunkSessionStatus::Received)));
     }
 }
@@ -0,0 +1,358 @@
+use crate::ChunkError;
+
+/**
+ * Width of the integers on the wire: data length in the header
+ * and the chunk counter in the meta of every chunk
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetaWidth {
+    U16,
+    U32,
+    U64,
+}
+
+/**
+ * Width of the data length in the header, see `Chunk::with_length_width`
+ */
+pub type LengthWidth = MetaWidth;
+
+/**
+ * Width of the chunk counter in meta, see `Chunk::with_counter_width`
+ */
+pub type CounterWidth = MetaWidth;
+
+/**
+ * Byte order of the header length and of the meta counter
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Default for MetaWidth {
+    /**
+     * U64 whatever the target, frames of a 64-bit host decode on a 32-bit MCU
+     * and back. Values too large for the `usize` of the receiver fail
+     * with `InvalidMetaSize`
+     */
+    fn default() -> Self {
+        MetaWidth::U64
+    }
+}
+
+impl MetaWidth {
+    pub const fn size(self) -> usize {
+        match self {
+            MetaWidth::U16 => core::mem::size_of::<u16>(),
+            MetaWidth::U32 => core::mem::size_of::<u32>(),
+            MetaWidth::U64 => core::mem::size_of::<u64>(),
+        }
+    }
+
+    pub const fn max(self) -> u64 {
+        match self {
+            MetaWidth::U16 => u16::MAX as u64,
+            MetaWidth::U32 => u32::MAX as u64,
+            MetaWidth::U64 => u64::MAX,
+        }
+    }
+
+    /**
+     * Most significant bit of this width
+     */
+    pub(crate) const fn top_bit(self) -> u64 {
+        1 << (self.size() * 8 - 1)
+    }
+
+    /**
+     * Sets the top bit of a value written with this width
+     */
+    pub(crate) fn set_top_bit(self, endianness: Endianness, out: &mut [u8]) {
+        match endianness {
+            Endianness::Little => out[self.size() - 1] |= 0x80,
+            Endianness::Big => out[0] |= 0x80,
+        }
+    }
+
+    /**
+     * Reads value with its top bit taken as a flag
+     */
+    pub(crate) fn read_flagged(
+        self,
+        bytes: &[u8],
+        endianness: Endianness,
+    ) -> Result<(usize, bool), ChunkError> {
+        let mut value = [0; core::mem::size_of::<u64>()];
+        let len = bytes.len().min(self.size());
+        value[..len].copy_from_slice(&bytes[..len]);
+        let top = match endianness {
+            Endianness::Little => self.size() - 1,
+            Endianness::Big => 0,
+        };
+        let flag = value[top] & 0x80 != 0;
+        value[top] &= 0x7F;
+        Ok((self.read(&value[..len], endianness)?, flag))
+    }
+
+    /**
+     * Fails when value can't be represented with this width
+     */
+    pub fn check(self, value: usize) -> Result<(), ChunkError> {
+        if value as u64 > self.max() {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: bytes_needed(value as u64),
+                got: self.size(),
+            });
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes value in little-endian order, returns the number of bytes written
+     */
+    pub fn encode(self, value: usize, out: &mut [u8]) -> Result<usize, ChunkError> {
+        self.write(value, Endianness::Little, out)
+    }
+
+    /**
+     * Writes value in the given byte order, returns the number of bytes written
+     */
+    pub fn write(
+        self,
+        value: usize,
+        endianness: Endianness,
+        out: &mut [u8],
+    ) -> Result<usize, ChunkError> {
+        self.check(value)?;
+        if out.len() < self.size() {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: self.size(),
+                got: out.len(),
+            });
+        }
+        self.put(value as u64, endianness, out);
+        Ok(self.size())
+    }
+
+    /**
+     * Writes the low bytes of value without checking it fits
+     */
+    pub(crate) fn put(self, value: u64, endianness: Endianness, out: &mut [u8]) {
+        match endianness {
+            Endianness::Little => {
+                out[..self.size()].copy_from_slice(&value.to_le_bytes()[..self.size()])
+            }
+            Endianness::Big => out[..self.size()]
+                .copy_from_slice(&value.to_be_bytes()[core::mem::size_of::<u64>() - self.size()..]),
+        }
+    }
+
+    /**
+     * Reads little-endian value
+     */
+    pub fn decode(self, bytes: &[u8]) -> Result<usize, ChunkError> {
+        self.read(bytes, Endianness::Little)
+    }
+
+    /**
+     * Reads value in the given byte order
+     */
+    pub fn read(self, bytes: &[u8], endianness: Endianness) -> Result<usize, ChunkError> {
+        if bytes.len() < self.size() {
+            return Err(ChunkError::InvalidMetaSize {
+                expected: self.size(),
+                got: bytes.len(),
+            });
+        }
+        let mut value = [0; core::mem::size_of::<u64>()];
+        let value = match endianness {
+            Endianness::Little => {
+                value[..self.size()].copy_from_slice(&bytes[..self.size()]);
+                u64::from_le_bytes(value)
+            }
+            Endianness::Big => {
+                value[core::mem::size_of::<u64>() - self.size()..]
+                    .copy_from_slice(&bytes[..self.size()]);
+                u64::from_be_bytes(value)
+            }
+        };
+        usize::try_from(value).map_err(|_| ChunkError::InvalidMetaSize {
+            expected: bytes_needed(value),
+            got: core::mem::size_of::<usize>(),
+        })
+    }
+}
+
+fn bytes_needed(value: u64) -> usize {
+    (u64::BITS - value.leading_zeros()).div_ceil(8) as usize
+}
+
+/**
+ * Longest LEB128 varint of a u64
+ */
+pub(crate) const MAX_VARINT_LEN: usize = 10;
+
+/**
+ * Counter of the abort frame of `Chunk::encode_abort`: the highest one the
+ * counter holds with its flag bit clear, capped to half of usize for varints
+ * and u64. Data chunks stay below it, see `Chunk::max_data_len`
+ */
+pub(crate) fn abort_counter(width: CounterWidth, flagged: bool, varint: bool) -> usize {
+    let max = match (varint, flagged) {
+        (true, _) => u64::MAX,
+        (false, true) => width.top_bit() - 1,
+        (false, false) => width.max(),
+    };
+    max.min((usize::MAX >> 1) as u64) as usize
+}
+
+/**
+ * Bytes of the shortest varint holding value, plus a flag bit on top of it
+ * when flagged is set
+ */
+pub(crate) const fn varint_len(value: u64, flagged: bool) -> usize {
+    let bits = u64::BITS - value.leading_zeros() + flagged as u32;
+    if bits == 0 {
+        1
+    } else {
+        bits.div_ceil(7) as usize
+    }
+}
+
+/**
+ * Most significant bit a varint of size bytes carries,
+ * the flag bit of a flagged value
+ */
+pub(crate) const fn varint_top_bit(size: usize) -> u64 {
+    let bit = 7 * size - 1;
+    1 << if bit < 63 { bit } else { 63 }
+}
+
+/**
+ * Writes value as LEB128 varint padded to size bytes, so every frame of
+ * a message keeps the same layout. value has to fit `varint_len()`
+ */
+pub(crate) fn put_varint(mut value: u64, size: usize, out: &mut [u8]) {
+    for (i, byte) in out[..size].iter_mut().enumerate() {
+        let more = if i + 1 < size { 0x80 } else { 0 };
+        *byte = (value & 0x7F) as u8 | more;
+        value >>= 7;
+    }
+}
+
+/**
+ * Reads LEB128 varint, padded or not, returns value and its size in bytes
+ */
+pub(crate) fn read_varint(bytes: &[u8]) -> Result<(u64, usize), ChunkError> {
+    let mut value = 0;
+    for (i, &byte) in bytes.iter().take(MAX_VARINT_LEN).enumerate() {
+        value |= u64::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(ChunkError::InvalidMetaSize {
+        expected: (bytes.len() + 1).min(MAX_VARINT_LEN),
+        got: bytes.len(),
+    })
+}
+
+/**
+ * Reads varint, flagged with `varint_top_bit()` when flagged is set,
+ * returns value, flag and size in bytes
+ */
+pub(crate) fn read_varint_flagged(
+    bytes: &[u8],
+    flagged: bool,
+) -> Result<(usize, bool, usize), ChunkError> {
+    let (mut value, size) = read_varint(bytes)?;
+    let top_bit = varint_top_bit(size);
+    let flag = flagged && value & top_bit != 0;
+    if flagged {
+        value &= !top_bit;
+    }
+    let value = usize::try_from(value).map_err(|_| ChunkError::InvalidMetaSize {
+        expected: bytes_needed(value),
+        got: core::mem::size_of::<usize>(),
+    })?;
+    Ok((value, flag, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let mut buf = [0; 8];
+        assert_eq!(MetaWidth::U16.encode(0x0102, &mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], [0x02, 0x01]);
+        assert_eq!(MetaWidth::U16.decode(&buf).unwrap(), 0x0102);
+        assert_eq!(
+            MetaWidth::U32
+                .write(0x0102, Endianness::Big, &mut buf)
+                .unwrap(),
+            4
+        );
+        assert_eq!(buf[..4], [0, 0, 0x01, 0x02]);
+        assert_eq!(MetaWidth::U32.read(&buf, Endianness::Big).unwrap(), 0x0102);
+        assert!(matches!(
+            MetaWidth::U16.encode(0x10000, &mut buf),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+        assert!(matches!(
+            MetaWidth::U32.decode(&buf[..3]),
+            Err(ChunkError::InvalidMetaSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_default_width() {
+        assert_eq!(MetaWidth::default(), MetaWidth::U64);
+        let mut buf = [0; 8];
+        MetaWidth::U64.put(1 << 32, Endianness::Little, &mut buf);
+        // a 32-bit receiver can't hold the value, anything wider reads it
+        match usize::try_from(1u64 << 32) {
+            Ok(value) => assert_eq!(MetaWidth::default().decode(&buf).unwrap(), value),
+            Err(_) => assert!(matches!(
+                MetaWidth::default().decode(&buf),
+                Err(ChunkError::InvalidMetaSize {
+                    expected: 5,
+                    got: 4
+                })
+            )),
+        }
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut buf = [0; MAX_VARINT_LEN];
+        assert_eq!(varint_len(0, false), 1);
+        assert_eq!(varint_len(0x7F, false), 1);
+        assert_eq!(varint_len(0x7F, true), 2);
+        assert_eq!(varint_len(300, false), 2);
+        assert_eq!(varint_len(u64::MAX, false), MAX_VARINT_LEN);
+
+        put_varint(300, 2, &mut buf);
+        assert_eq!(buf[..2], [0xAC, 0x02]);
+        assert_eq!(read_varint(&buf).unwrap(), (300, 2));
+        put_varint(5, 3, &mut buf);
+        assert_eq!(buf[..3], [0x85, 0x80, 0x00]);
+        assert_eq!(read_varint(&buf).unwrap(), (5, 3));
+        put_varint(u64::MAX, MAX_VARINT_LEN, &mut buf);
+        assert_eq!(read_varint(&buf).unwrap(), (u64::MAX, MAX_VARINT_LEN));
+
+        put_varint(5 | varint_top_bit(1), 1, &mut buf);
+        assert_eq!(read_varint_flagged(&buf, true).unwrap(), (5, true, 1));
+        assert_eq!(read_varint_flagged(&buf, false).unwrap(), (0x45, false, 1));
+        assert!(matches!(
+            read_varint(&[0x80, 0x80]),
+            Err(ChunkError::InvalidMetaSize {
+                expected: 3,
+                got: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,252 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::{Chunk, ChunkError, Dechunker, Frame};
+
+/**
+ * Interleaves several messages over a single channel, one chunk of every
+ * active transfer in turn instead of draining one message before the next.
+ * Transfers of a higher `Chunk::with_priority` preempt the others,
+ * transfers of the same priority take turns.
+ * Transfers are told apart by topic: every frame carries the topic
+ * as its session id (see `Chunk::with_session_id`), the priority in its
+ * high byte, which the receiving `Demultiplexer` routes by.
+ */
+#[derive(Default)]
+pub struct Multiplexer<'a> {
+    chunks: Vec<Chunk<'a>>,
+    cursor: usize,
+}
+
+impl<'a> Multiplexer<'a> {
+    pub fn new() -> Self {
+        Multiplexer {
+            ..Default::default()
+        }
+    }
+
+    /**
+     * Adds a transfer, its session id is replaced by the priority and the topic,
+     * `u16::from_be_bytes([priority, topic])`.
+     * Fails with `InvalidTopic` while another transfer of the same topic
+     * is still active and with `ChunkSizeTooSmall` when max_chunk_size
+     * can't hold the session id
+     */
+    pub fn push(&mut self, chunk: Chunk<'a>) -> Result<(), ChunkError> {
+        if self
+            .chunks
+            .iter()
+            .any(|active| active.topic == chunk.topic && active.len() > 0)
+        {
+            return Err(ChunkError::InvalidTopic(chunk.topic));
+        }
+        let session_id = u16::from_be_bytes([chunk.priority, chunk.topic]);
+        let chunk = chunk.with_session_id(session_id);
+        chunk.validate()?;
+        self.chunks.push(chunk);
+        Ok(())
+    }
+
+    /**
+     * Transfers with chunks left to send
+     */
+    pub fn active(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.len() > 0).count()
+    }
+
+    /**
+     * Writes the wire frame of the next chunk into out, returns its length,
+     * None when every transfer was sent
+     */
+    pub fn encode_next(&mut self, out: &mut [u8]) -> Option<Result<usize, ChunkError>> {
+        let (index, counter) = self.advance()?;
+        Some(self.chunks[index].encode_into(counter, out))
+    }
+
+    /**
+     * Index of the transfer in turn and its next counter: the first transfer
+     * of the highest priority from the cursor on, finished transfers are
+     * dropped on the way
+     */
+    fn advance(&mut self) -> Option<(usize, usize)> {
+        let mut index = 0;
+        while index < self.chunks.len() {
+            if self.chunks[index].len() > 0 {
+                index += 1;
+                continue;
+            }
+            self.chunks.remove(index);
+            if index < self.cursor {
+                self.cursor -= 1;
+            }
+        }
+        let top = self.chunks.iter().map(|chunk| chunk.priority).max()?;
+        let count = self.chunks.len();
+        let index = (0..count)
+            .map(|offset| (self.cursor + offset) % count)
+            .find(|&index| self.chunks[index].priority == top)?;
+        let (_, counter) = self.chunks[index].next()?;
+        self.cursor = index + 1;
+        Some((index, counter))
+    }
+}
+
+impl<'a> Iterator for Multiplexer<'a> {
+    type Item = Frame<'a>;
+
+    /**
+     * Next chunk in round-robin order, wire bytes of it come from `encode_next`
+     */
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, counter) = self.advance()?;
+        self.chunks[index].chunk_at(counter)
+    }
+}
+
+/**
+ * Receiver side of `Multiplexer`, reassembles interleaved messages
+ * in a separate `Dechunker` per topic
+ */
+pub struct Demultiplexer {
+    template: Dechunker,
+    dechunkers: BTreeMap<u16, Dechunker>,
+}
+
+impl Demultiplexer {
+    /**
+     * Every transfer is reassembled by a copy of template, a `Dechunker`
+     * configured like the senders, session ids are enabled on it
+     */
+    pub fn new(template: Dechunker) -> Self {
+        Demultiplexer {
+            template: template.with_session_ids(),
+            dechunkers: BTreeMap::new(),
+        }
+    }
+
+    /**
+     * Accepts a single wire frame of any transfer, returns topic
+     * and reassembled data once the message of that topic is complete
+     */
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<(u8, Vec<u8>)>, ChunkError> {
+        let session_id = self.template.decode_frame(frame)?.session_id.unwrap_or(0);
+        let dechunker = self
+            .dechunkers
+            .entry(session_id)
+            .or_insert_with(|| self.template.clone());
+        let data = match dechunker.push(frame)? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let topic = dechunker.topic().unwrap_or(0);
+        self.dechunkers.remove(&session_id);
+        Ok(Some((topic, data)))
+    }
+
+    /**
+     * Priority of the transfer frame belongs to, to deliver urgent frames
+     * first before pushing them, see `Multiplexer::push`
+     */
+    pub fn priority(&self, frame: &[u8]) -> Result<u8, ChunkError> {
+        let session_id = self.template.decode_frame(frame)?.session_id.unwrap_or(0);
+        Ok(session_id.to_be_bytes()[0])
+    }
+
+    /**
+     * Transfers with some but not all chunks received
+     */
+    pub fn in_progress(&self) -> usize {
+        self.dechunkers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_round_robin() {
+        let log = [1; 600];
+        let telemetry = [2; 100];
+        let config = [3; 400];
+        let mut mux = Multiplexer::new();
+        mux.push(Chunk::new(250, 1, &log)).unwrap();
+        mux.push(Chunk::new(250, 2, &telemetry)).unwrap();
+        mux.push(Chunk::new(250, 3, &config)).unwrap();
+        assert!(matches!(
+            mux.push(Chunk::new(250, 1, &log)),
+            Err(ChunkError::InvalidTopic(1))
+        ));
+        assert_eq!(mux.active(), 3);
+
+        let order: Vec<_> = mux
+            .by_ref()
+            .map(|frame| (frame.topic, frame.counter))
+            .collect();
+        assert_eq!(order, [(1, 0), (2, 0), (3, 0), (1, 1), (3, 1), (1, 2)]);
+        assert_eq!(mux.active(), 0);
+        mux.push(Chunk::new(250, 1, &log)).unwrap();
+    }
+
+    #[test]
+    fn test_priority() {
+        let bulk = [1; 1000];
+        let telemetry = [2; 400];
+        let log = [3; 400];
+        let mut mux = Multiplexer::new();
+        mux.push(Chunk::new(250, 1, &bulk)).unwrap();
+        mux.push(Chunk::new(250, 2, &telemetry).with_priority(1))
+            .unwrap();
+        let first = mux.next().unwrap();
+        assert_eq!((first.topic, first.counter), (2, 0));
+        assert_eq!(first.session_id, Some(0x0102));
+
+        // a log transfer of the same priority takes turns with telemetry
+        mux.push(Chunk::new(250, 3, &log).with_priority(1)).unwrap();
+        let order: Vec<_> = mux.map(|frame| (frame.topic, frame.counter)).collect();
+        assert_eq!(
+            order,
+            [
+                (3, 0),
+                (2, 1),
+                (3, 1),
+                (1, 0),
+                (1, 1),
+                (1, 2),
+                (1, 3),
+                (1, 4)
+            ]
+        );
+
+        let mut mux = Multiplexer::new();
+        mux.push(Chunk::new(250, 1, &bulk)).unwrap();
+        mux.push(Chunk::new(250, 3, &log).with_priority(7)).unwrap();
+        let demux = Demultiplexer::new(Dechunker::new());
+        let mut frame = [0; 250];
+        let len = mux.encode_next(&mut frame).unwrap().unwrap();
+        assert_eq!(demux.priority(&frame[..len]).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_demultiplex() {
+        let log: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let telemetry = vec![2; 100];
+        let config: Vec<u8> = (0..400).map(|i| (i * 3) as u8).collect();
+        let mut mux = Multiplexer::new();
+        mux.push(Chunk::new(250, 1, &log).with_crc()).unwrap();
+        mux.push(Chunk::new(250, 2, &telemetry).with_crc()).unwrap();
+        mux.push(Chunk::new(250, 3, &config).with_crc()).unwrap();
+
+        let mut demux = Demultiplexer::new(Dechunker::new().with_crc());
+        let mut completed = Vec::new();
+        let mut frame = [0; 250];
+        while let Some(len) = mux.encode_next(&mut frame) {
+            if let Some(message) = demux.push(&frame[..len.unwrap()]).unwrap() {
+                completed.push(message);
+            }
+        }
+        assert_eq!(completed, [(2, telemetry), (3, config), (1, log)]);
+        assert_eq!(demux.in_progress(), 0);
+    }
+}
@@ -0,0 +1,75 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::Chunk;
+
+/**
+ * Iterator over the wire frames of a `Chunk` owning its data,
+ * `Send + 'static` to hand a transfer off to another thread, see `Chunk::into_owned`
+ */
+pub struct OwnedChunkIter {
+    chunk: Chunk<'static, Arc<[u8]>>,
+}
+
+impl OwnedChunkIter {
+    pub fn chunk(&self) -> &Chunk<'static, Arc<[u8]>> {
+        &self.chunk
+    }
+}
+
+impl<B: AsRef<[u8]>> Chunk<'static, B> {
+    /**
+     * Copies the whole data once into an `Arc<[u8]>`, frames are then encoded
+     * one `Vec<u8>` per `next()` from the iteration position of the chunk on.
+     * Options must not borrow, as with `Chunk::from_data` and `ChunkBuilder`
+     * (`Chunk::new` ties them to the borrow of data)
+     */
+    pub fn into_owned(self) -> OwnedChunkIter {
+        OwnedChunkIter {
+            chunk: self.map_data(|data| Arc::from(data.as_ref())),
+        }
+    }
+}
+
+impl Iterator for OwnedChunkIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunk.counter >= self.chunk.back_counter() {
+            return None;
+        }
+        let frame = self.chunk.frame_vec(self.chunk.counter, true)?;
+        self.chunk.inc_counter();
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.chunk.back_counter().saturating_sub(self.chunk.counter);
+        (remaining, Some(remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChunkBuilder;
+
+    #[test]
+    fn test_into_owned() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = ChunkBuilder::new()
+            .max_chunk_size(250)
+            .topic(0x10)
+            .data(&data[..])
+            .with_crc()
+            .build()
+            .unwrap();
+        let expected = chunk.to_frames();
+        let owned = chunk.into_owned();
+        drop(data);
+        let frames = std::thread::spawn(move || owned.collect::<Vec<_>>())
+            .join()
+            .unwrap();
+        assert_eq!(frames, expected);
+    }
+}
@@ -0,0 +1,86 @@
+use core::time::Duration;
+
+/**
+ * How long `ChunkSession::poll` waits for an ack before re-emitting a chunk
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryPolicy {
+    /**
+     * Same timeout for every retry
+     */
+    Fixed(Duration),
+    /**
+     * Timeout doubles with every retry, starting at initial, capped at max
+     */
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl RetryPolicy {
+    /**
+     * Timeout to wait after retry re-emits (0 for the first transmission)
+     */
+    pub fn next_timeout(&self, retry: u8) -> Duration {
+        match *self {
+            RetryPolicy::Fixed(timeout) => timeout,
+            RetryPolicy::Exponential { initial, max } => 1u32
+                .checked_shl(retry.into())
+                .and_then(|factor| initial.checked_mul(factor))
+                .map_or(max, |timeout| timeout.min(max)),
+        }
+    }
+}
+
+/**
+ * Point in time `ChunkSession::poll` measures timeouts with,
+ * implemented for `std::time::Instant` and for `u64` millisecond ticks,
+ * embedded users can implement it for their own tick counter
+ */
+pub trait Timestamp: Copy {
+    /**
+     * Time passed from earlier to self, zero when earlier is later
+     */
+    fn elapsed_since(&self, earlier: Self) -> Duration;
+}
+
+/**
+ * Millisecond tick counter, wrapping around is taken into account
+ */
+impl Timestamp for u64 {
+    fn elapsed_since(&self, earlier: Self) -> Duration {
+        Duration::from_millis(self.wrapping_sub(earlier))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Timestamp for std::time::Instant {
+    fn elapsed_since(&self, earlier: Self) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_timeout() {
+        let fixed = RetryPolicy::Fixed(Duration::from_millis(100));
+        assert_eq!(fixed.next_timeout(0), Duration::from_millis(100));
+        assert_eq!(fixed.next_timeout(u8::MAX), Duration::from_millis(100));
+
+        let exponential = RetryPolicy::Exponential {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+        };
+        assert_eq!(exponential.next_timeout(0), Duration::from_millis(100));
+        assert_eq!(exponential.next_timeout(1), Duration::from_millis(200));
+        assert_eq!(exponential.next_timeout(3), Duration::from_millis(800));
+        assert_eq!(exponential.next_timeout(4), Duration::from_secs(1));
+        assert_eq!(exponential.next_timeout(u8::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_ticks_wrap() {
+        assert_eq!(5u64.elapsed_since(u64::MAX - 4), Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,369 @@
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use crate::crc::crc32_append;
+use crate::{Checksum, Chunk, ChunkError, CounterWidth, Endianness, LengthWidth, MetaWidth};
+
+/**
+ * Chunks the concatenation of several fragments as a single message,
+ * see `Chunk::from_slices`. Chunk boundaries go across fragment edges:
+ * payloads within a fragment are borrowed from it, a payload straddling
+ * fragments is copied into an owned buffer. The layout is the one of `Chunk`,
+ * so `encode_into` writes frames straight from the fragments that are the
+ * same as `Chunk::encode_into` gives for the concatenated data with the same
+ * options. Compression isn't available, it needs the data in one piece
+ */
+#[derive(Debug, Clone)]
+pub struct ScatterChunk<'a> {
+    chunk: Chunk<'a>,
+    slices: &'a [&'a [u8]],
+}
+
+impl<'a> ScatterChunk<'a> {
+    pub(crate) fn new(max_chunk_size: usize, topic: u8, slices: &'a [&'a [u8]]) -> Self {
+        let len = slices.iter().map(|slice| slice.len()).sum();
+        ScatterChunk {
+            chunk: Chunk::gathered(max_chunk_size, topic, len),
+            slices,
+        }
+    }
+
+    /**
+     * See `Chunk::with_crc`
+     */
+    pub fn with_crc(mut self) -> Self {
+        self.chunk = self.chunk.with_crc();
+        self
+    }
+
+    /**
+     * See `Chunk::with_checksum`
+     */
+    pub fn with_checksum(mut self, checksum: &'a dyn Checksum) -> Self {
+        self.chunk = self.chunk.with_checksum(checksum);
+        self
+    }
+
+    /**
+     * See `Chunk::with_meta_width`
+     */
+    pub fn with_meta_width(mut self, meta_width: MetaWidth) -> Self {
+        self.chunk = self.chunk.with_meta_width(meta_width);
+        self
+    }
+
+    /**
+     * See `Chunk::with_length_width`
+     */
+    pub fn with_length_width(mut self, length_width: LengthWidth) -> Self {
+        self.chunk = self.chunk.with_length_width(length_width);
+        self
+    }
+
+    /**
+     * See `Chunk::with_counter_width`
+     */
+    pub fn with_counter_width(mut self, counter_width: CounterWidth) -> Self {
+        self.chunk = self.chunk.with_counter_width(counter_width);
+        self
+    }
+
+    /**
+     * See `Chunk::with_varint`
+     */
+    pub fn with_varint(mut self) -> Self {
+        self.chunk = self.chunk.with_varint();
+        self
+    }
+
+    /**
+     * See `Chunk::with_endianness`
+     */
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.chunk = self.chunk.with_endianness(endianness);
+        self
+    }
+
+    /**
+     * See `Chunk::with_version`
+     */
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.chunk = self.chunk.with_version(version);
+        self
+    }
+
+    /**
+     * See `Chunk::with_last_flag`
+     */
+    pub fn with_last_flag(mut self) -> Self {
+        self.chunk = self.chunk.with_last_flag();
+        self
+    }
+
+    /**
+     * See `Chunk::with_session_id`
+     */
+    pub fn with_session_id(mut self, session_id: u16) -> Self {
+        self.chunk = self.chunk.with_session_id(session_id);
+        self
+    }
+
+    /**
+     * See `Chunk::with_chunk_length`
+     */
+    pub fn with_chunk_length(mut self) -> Self {
+        self.chunk = self.chunk.with_chunk_length();
+        self
+    }
+
+    /**
+     * See `Chunk::with_chunk_count`
+     */
+    pub fn with_chunk_count(mut self) -> Self {
+        self.chunk = self.chunk.with_chunk_count();
+        self
+    }
+
+    /**
+     * See `Chunk::with_repeat_header`
+     */
+    pub fn with_repeat_header(mut self) -> Self {
+        self.chunk = self.chunk.with_repeat_header();
+        self
+    }
+
+    /**
+     * See `Chunk::with_terminator`
+     */
+    pub fn with_terminator(mut self) -> Self {
+        self.chunk = self.chunk.with_terminator();
+        self
+    }
+
+    /**
+     * See `Chunk::with_alignment`
+     */
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.chunk = self.chunk.with_alignment(alignment);
+        self
+    }
+
+    /**
+     * See `Chunk::with_header_flags`
+     */
+    pub fn with_header_flags(mut self) -> Self {
+        self.chunk = self.chunk.with_header_flags();
+        self
+    }
+
+    /**
+     * See `Chunk::with_message_crc`, the CRC goes over the fragments in order
+     */
+    pub fn with_message_crc(mut self) -> Self {
+        let crc = self
+            .slices
+            .iter()
+            .fold(0, |crc, slice| crc32_append(crc, slice));
+        self.chunk.message_crc = Some(crc);
+        self
+    }
+
+    /**
+     * Bytes of all fragments together, the length in the header
+     */
+    pub fn len(&self) -> usize {
+        self.chunk.data_len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn counter(&self) -> usize {
+        self.chunk.counter()
+    }
+
+    /**
+     * See `Chunk::total_chunks`
+     */
+    pub fn total_chunks(&self) -> usize {
+        self.chunk.total_chunks()
+    }
+
+    /**
+     * Payload of the chunk, borrowed unless it straddles fragments,
+     * None past the end of data or when misconfigured, see `Chunk::chunk`
+     */
+    pub fn payload(&self, counter: usize) -> Option<Cow<'a, [u8]>> {
+        let range = self.chunk.try_range(counter).ok()??;
+        let mut pieces = self.pieces(range.clone());
+        match pieces.next() {
+            Some(piece) if piece.len() == range.len() => Some(Cow::Borrowed(piece)),
+            None => Some(Cow::Borrowed(&[])),
+            first => {
+                let mut payload = Vec::with_capacity(range.len());
+                for piece in first.into_iter().chain(pieces) {
+                    payload.extend_from_slice(piece);
+                }
+                Some(Cow::Owned(payload))
+            }
+        }
+    }
+
+    /**
+     * Writes the wire frame of the chunk into out, returns its length,
+     * fails as `Chunk::encode_into` does
+     */
+    pub fn encode_into(&self, counter: usize, out: &mut [u8]) -> Result<usize, ChunkError> {
+        self.chunk
+            .encode_frame_with(counter, true, out, |range, mut payload| {
+                for piece in self.pieces(range) {
+                    let (head, rest) = payload.split_at_mut(piece.len());
+                    head.copy_from_slice(piece);
+                    payload = rest;
+                }
+                Ok(())
+            })
+    }
+
+    /**
+     * Parts of the fragments covering range, in order
+     */
+    fn pieces(&self, range: Range<usize>) -> impl Iterator<Item = &'a [u8]> {
+        let slices: &'a [&'a [u8]] = self.slices;
+        let mut offset = 0;
+        slices.iter().filter_map(move |slice| {
+            let (start, end) = (offset, offset + slice.len());
+            offset = end;
+            let from = range.start.max(start);
+            let to = range.end.min(end);
+            (from < to).then(|| &slice[from - start..to - start])
+        })
+    }
+}
+
+impl<'a> Iterator for ScatterChunk<'a> {
+    type Item = (Cow<'a, [u8]>, usize);
+
+    /**
+     * Next payload and its number, owned only when it straddles fragments
+     */
+    fn next(&mut self) -> Option<Self::Item> {
+        let counter = self.chunk.counter;
+        let payload = self.payload(counter)?;
+        self.chunk.counter += 1;
+        Some((payload, counter))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.total_chunks().saturating_sub(self.counter());
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for ScatterChunk<'_> {}
+
+impl FusedIterator for ScatterChunk<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chunk, Dechunker};
+
+    #[test]
+    fn test_from_slices() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let slices: [&[u8]; 4] = [&data[..100], &data[100..240], &data[240..240], &data[240..]];
+        let scatter = Chunk::from_slices(250, 0x10, &slices).with_crc();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        assert_eq!(scatter.len(), 1000);
+        assert_eq!(scatter.total_chunks(), chunk.total_chunks());
+
+        let payloads: Vec<_> = scatter.clone().collect();
+        assert_eq!(payloads.len(), 5);
+        // 0..231 straddles the first two fragments, 231..471 the next ones
+        assert!(matches!(payloads[0].0, Cow::Owned(_)));
+        assert!(matches!(payloads[1].0, Cow::Owned(_)));
+        assert!(matches!(payloads[2].0, Cow::Borrowed(_)));
+        for ((scattered, counter), (payload, expected)) in payloads.iter().zip(chunk.clone()) {
+            assert_eq!((&scattered[..], *counter), (payload, expected));
+        }
+
+        let mut dechunker = Dechunker::new().with_crc();
+        let mut frame = [0; 250];
+        let mut result = None;
+        for (counter, expected) in chunk.to_frames().iter().enumerate() {
+            let len = scatter.encode_into(counter, &mut frame).unwrap();
+            assert_eq!(&frame[..len], &expected[..]);
+            result = dechunker.push(&frame[..len]).unwrap();
+        }
+        assert_eq!(result.unwrap(), data);
+        assert!(matches!(
+            scatter.encode_into(5, &mut frame),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn test_from_slices_empty() {
+        let scatter = Chunk::from_slices(250, 0x10, &[]);
+        assert!(scatter.is_empty());
+        let payloads: Vec<_> = scatter.collect();
+        assert!(matches!(payloads[..], [(Cow::Borrowed(&[]), 0)]));
+
+        let slices: [&[u8]; 1] = [&[7; 10]];
+        let scatter = Chunk::from_slices(10, 0x10, &slices);
+        assert_eq!(scatter.total_chunks(), 0);
+        assert!(matches!(
+            scatter.encode_into(0, &mut [0; 10]),
+            Err(ChunkError::ChunkSizeTooSmall { min: 18 })
+        ));
+    }
+
+    #[test]
+    fn test_from_slices_options() {
+        let data: Vec<u8> = (0..700).map(|i| (i * 7) as u8).collect();
+        let slices: [&[u8]; 3] = [&data[..5], &data[5..400], &data[400..]];
+        let scatter = Chunk::from_slices(128, 0x22, &slices)
+            .with_crc()
+            .with_varint()
+            .with_session_id(9)
+            .with_chunk_length()
+            .with_chunk_count()
+            .with_header_flags()
+            .with_terminator()
+            .with_alignment(8)
+            .with_message_crc();
+        let chunk = Chunk::new(128, 0x22, &data)
+            .with_crc()
+            .with_varint()
+            .with_session_id(9)
+            .with_chunk_length()
+            .with_chunk_count()
+            .with_header_flags()
+            .with_terminator()
+            .with_alignment(8)
+            .with_message_crc();
+        assert_eq!(scatter.total_chunks(), chunk.total_chunks());
+
+        let mut frame = [0; 128];
+        let frames = chunk.to_frames();
+        for (counter, expected) in frames.iter().enumerate() {
+            let len = scatter.encode_into(counter, &mut frame).unwrap();
+            assert_eq!(&frame[..len], &expected[..]);
+        }
+        assert!(matches!(
+            scatter.encode_into(frames.len(), &mut frame),
+            Err(ChunkError::CounterOutOfRange)
+        ));
+
+        let misaligned = Chunk::from_slices(128, 0x22, &slices).with_alignment(3);
+        assert!(misaligned.payload(0).is_none());
+        assert!(matches!(
+            misaligned.encode_into(0, &mut frame),
+            Err(ChunkError::InvalidAlignment(3))
+        ));
+    }
+}
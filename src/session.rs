@@ -0,0 +1,577 @@
+use crate::{Chunk, ChunkError, ChunkSessionStatus, ChunkStatus, Frame, RetryPolicy, Timestamp};
+
+/**
+ * Largest `ChunkSession::with_window_size`, the size of the ring of chunks in flight
+ */
+pub const MAX_WINDOW: usize = 32;
+
+/**
+ * Chunk in flight, kept in the ring at its counter modulo `MAX_WINDOW`
+ */
+#[derive(Clone, Copy)]
+struct Slot<T> {
+    retry: u8,
+    sent_at: Option<T>,
+    acked: bool,
+}
+
+impl<T> Slot<T> {
+    const EMPTY: Self = Slot {
+        retry: 0,
+        sent_at: None,
+        acked: false,
+    };
+}
+
+/**
+ * Sliding-window transfer of a `Chunk`: up to `with_window_size()` chunks
+ * are handed out before their acks, 1 by default which is stop-and-wait,
+ * timeouts re-emit unacknowledged chunks until their retries are exhausted.
+ * T is the `Timestamp` `poll` is driven with.
+ */
+pub struct ChunkSession<'a, T = u64> {
+    chunk: Chunk<'a>,
+    /**
+     * Chunks base..next are in flight, acked ones among them are marked in the ring
+     */
+    base: usize,
+    next: usize,
+    window_size: usize,
+    slots: [Slot<T>; MAX_WINDOW],
+    max_retries: u8,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl<'a> ChunkSession<'a> {
+    pub fn new(chunk: Chunk<'a>) -> Self {
+        let counter = chunk.counter();
+        ChunkSession {
+            chunk,
+            base: counter,
+            next: counter,
+            window_size: 1,
+            slots: [Slot::EMPTY; MAX_WINDOW],
+            max_retries: u8::MAX,
+            retry_policy: None,
+        }
+    }
+}
+
+impl<'a, T: Timestamp> ChunkSession<'a, T> {
+    /**
+     * Lets `poll` re-emit a chunk in flight once the timeout of policy
+     * elapsed, time is measured in U, e.g. `std::time::Instant`
+     */
+    pub fn with_retry_policy<U: Timestamp>(self, policy: RetryPolicy) -> ChunkSession<'a, U> {
+        let mut slots = [Slot::EMPTY; MAX_WINDOW];
+        for (slot, old) in slots.iter_mut().zip(self.slots) {
+            slot.retry = old.retry;
+            slot.acked = old.acked;
+        }
+        ChunkSession {
+            chunk: self.chunk,
+            base: self.base,
+            next: self.next,
+            window_size: self.window_size,
+            slots,
+            max_retries: self.max_retries,
+            retry_policy: Some(policy),
+        }
+    }
+
+    /**
+     * Timeouts fail with `OverflowRetryCounter` after max_retries re-emits
+     * of the same chunk, `u8::MAX` by default
+     */
+    pub fn with_max_retries(mut self, max_retries: u8) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /**
+     * Chunks handed out before the oldest of them is acknowledged,
+     * 1 to `MAX_WINDOW`, other values are clamped
+     */
+    pub fn with_window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size.clamp(1, MAX_WINDOW);
+        self
+    }
+
+    pub fn chunk(&self) -> &Chunk<'a> {
+        &self.chunk
+    }
+
+    /**
+     * Status of the chunk last handed out or re-emitted, `Sent` while it
+     * waits for ack, None before the first chunk
+     */
+    pub fn current(&self) -> Option<&ChunkStatus> {
+        self.chunk.status.number.map(|_| &self.chunk.status)
+    }
+
+    /**
+     * Chunks handed out and not acknowledged yet
+     */
+    pub fn in_flight(&self) -> usize {
+        (self.base..self.next)
+            .filter(|&counter| !self.slot(counter).acked)
+            .count()
+    }
+
+    fn slot(&self, counter: usize) -> &Slot<T> {
+        &self.slots[counter % MAX_WINDOW]
+    }
+
+    fn slot_mut(&mut self, counter: usize) -> &mut Slot<T> {
+        &mut self.slots[counter % MAX_WINDOW]
+    }
+
+    /**
+     * Next chunk to transmit, None while the window is full of chunks
+     * waiting for ack or when everything was sent
+     */
+    pub fn next_to_send(&mut self) -> Option<Frame<'a>> {
+        if self.next - self.base >= self.window_size || self.is_aborted() {
+            return None;
+        }
+        let (_, counter) = self.chunk.next()?;
+        *self.slot_mut(counter) = Slot::EMPTY;
+        self.next = counter + 1;
+        self.chunk.status.to_send(counter);
+        self.chunk.chunk_at(counter)
+    }
+
+    /**
+     * Acks chunk number, the window slides past every acknowledged chunk
+     * at its start. Acks of chunks not handed out yet fail with
+     * `UnexpectedChunkNumber` and leave the session untouched, repeated acks
+     * and late acks of an aborted session are ignored
+     */
+    pub fn on_ack(&mut self, number: usize) -> Result<(), ChunkError> {
+        if self.is_aborted() || number < self.base {
+            return Ok(());
+        }
+        if number >= self.next {
+            return Err(ChunkError::UnexpectedChunkNumber {
+                expected: self.chunk.status.number,
+                got: number,
+            });
+        }
+        if self.chunk.status.number == Some(number) {
+            self.chunk.status.to_received(number)?;
+        }
+        self.slot_mut(number).acked = true;
+        while self.base < self.next && self.slot(self.base).acked {
+            self.base += 1;
+        }
+        Ok(())
+    }
+
+    /**
+     * Re-emits the oldest chunk in flight, fails with `OverflowRetryCounter`
+     * once its max_retries are exhausted
+     */
+    pub fn on_timeout(&mut self) -> Result<Frame<'a>, ChunkError> {
+        let counter = (self.base..self.next)
+            .find(|&counter| !self.slot(counter).acked)
+            .ok_or(ChunkError::NoChunkInFlight)?;
+        self.retransmit(counter)
+    }
+
+    fn retransmit(&mut self, counter: usize) -> Result<Frame<'a>, ChunkError> {
+        let max_retries = self.max_retries;
+        let slot = self.slot_mut(counter);
+        if slot.retry >= max_retries {
+            return Err(ChunkError::OverflowRetryCounter);
+        }
+        slot.retry += 1;
+        let retry = slot.retry;
+        self.chunk.status.to_send(counter);
+        self.chunk.status.retry = retry;
+        self.chunk
+            .chunk_at(counter)
+            .ok_or(ChunkError::CounterOutOfRange)
+    }
+
+    /**
+     * Timer loop step: a chunk in flight again once the timeout of the retry
+     * policy elapsed since it was last emitted, oldest first, or else the
+     * next chunk while the window has room, None while there is nothing to send.
+     * Timers of chunks handed out by `next_to_send` start at this call.
+     * Without a retry policy chunks in flight are never re-emitted,
+     * fails like `on_timeout` once retries are exhausted
+     */
+    pub fn poll(&mut self, now: T) -> Result<Option<Frame<'a>>, ChunkError> {
+        let mut expired = None;
+        for counter in self.base..self.next {
+            let retry_policy = self.retry_policy;
+            let slot = self.slot_mut(counter);
+            if slot.acked {
+                continue;
+            }
+            let Some(sent_at) = slot.sent_at else {
+                // handed out by `next_to_send`, the timer starts now
+                slot.sent_at = Some(now);
+                continue;
+            };
+            let timed_out = retry_policy.is_some_and(|policy| {
+                now.elapsed_since(sent_at) >= policy.next_timeout(slot.retry)
+            });
+            if timed_out && expired.is_none() {
+                expired = Some(counter);
+            }
+        }
+        if let Some(counter) = expired {
+            let frame = self.retransmit(counter)?;
+            self.slot_mut(counter).sent_at = Some(now);
+            return Ok(Some(frame));
+        }
+        let frame = self.next_to_send();
+        if let Some(frame) = &frame {
+            self.slot_mut(frame.counter).sent_at = Some(now);
+        }
+        Ok(frame)
+    }
+
+    /**
+     * Starts the transfer over from the first chunk with cleared status and
+     * nothing in flight. The session id of the chunk, if set, is incremented,
+     * so a `Dechunker::with_session_ids` receiving the new attempt rejects
+     * stale chunks of the failed one with `SessionMismatch`. The id wraps
+     * from `u16::MAX` to 0, ids repeat after 65536 restarts
+     */
+    pub fn restart(&mut self) {
+        self.chunk.reset();
+        self.chunk.session_id = self.chunk.session_id.map(|id| id.wrapping_add(1));
+        self.base = 0;
+        self.next = 0;
+    }
+
+    /**
+     * Cancels the transfer: nothing is sent anymore and the status turns
+     * `ChunkSessionStatus::Aborted`, `restart()` starts over.
+     * Returns the abort frame for the receiver to drop what it reassembled,
+     * wire bytes come from `Chunk::encode_abort`, None when no chunk was
+     * handed out yet, the transfer is done or already aborted
+     */
+    pub fn abort(&mut self) -> Option<Frame<'a>> {
+        if self.is_done() || self.is_aborted() {
+            return None;
+        }
+        let started = self.chunk.status.number.is_some();
+        self.base = self.next;
+        self.chunk.status.session = Some(ChunkSessionStatus::Aborted);
+        started.then(|| Frame {
+            topic: self.chunk.topic,
+            counter: self.chunk.abort_counter(),
+            payload: &[],
+            data_offset: 0,
+            total_chunks: Some(self.chunk.total_chunks()),
+            session_id: self.chunk.session_id,
+            is_first: false,
+            is_last: true,
+        })
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        matches!(self.chunk.status.session, Some(ChunkSessionStatus::Aborted))
+    }
+
+    /**
+     * All chunks were sent and acknowledged
+     */
+    pub fn is_done(&self) -> bool {
+        self.base == self.next && self.chunk.counter() >= self.chunk.total_chunks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChunkSessionStatus, DechunkerRef};
+    use core::time::Duration;
+
+    #[test]
+    fn test_session() {
+        let data = [0; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data));
+        let mut sent = 0;
+        while let Some(frame) = session.next_to_send() {
+            assert_eq!(frame.counter, sent);
+            assert!(session.next_to_send().is_none());
+            assert!(matches!(
+                session.on_ack(frame.counter + 1),
+                Err(ChunkError::UnexpectedChunkNumber { .. })
+            ));
+            assert!(session.next_to_send().is_none());
+            session.on_ack(frame.counter).unwrap();
+            sent += 1;
+        }
+        assert_eq!(sent, 5);
+        assert!(session.is_done());
+    }
+
+    #[test]
+    fn test_session_timeout() {
+        let data = [0; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data));
+        assert!(matches!(
+            session.on_timeout(),
+            Err(ChunkError::NoChunkInFlight)
+        ));
+        let frame = session.next_to_send().unwrap();
+        for _ in 0..u8::MAX {
+            assert_eq!(session.on_timeout().unwrap(), frame);
+        }
+        assert!(matches!(
+            session.on_timeout(),
+            Err(ChunkError::OverflowRetryCounter)
+        ));
+        assert!(session.next_to_send().is_none());
+        assert!(!session.is_done());
+    }
+
+    #[test]
+    fn test_session_max_retries() {
+        let data = [0; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data)).with_max_retries(5);
+        session.next_to_send().unwrap();
+        for _ in 0..5 {
+            session.on_timeout().unwrap();
+        }
+        assert!(matches!(
+            session.on_timeout(),
+            Err(ChunkError::OverflowRetryCounter)
+        ));
+        session.on_ack(0).unwrap();
+        session.next_to_send().unwrap();
+        assert!(session.on_timeout().is_ok());
+    }
+
+    #[test]
+    fn test_session_current() {
+        let data = [0; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data));
+        assert!(session.current().is_none());
+        assert!(matches!(
+            session.on_ack(0),
+            Err(ChunkError::UnexpectedChunkNumber {
+                expected: None,
+                got: 0
+            })
+        ));
+
+        session.next_to_send().unwrap();
+        session.on_timeout().unwrap();
+        let current = session.current().unwrap();
+        assert_eq!(current.number, Some(0));
+        assert!(matches!(current.session, Some(ChunkSessionStatus::Sent)));
+        assert_eq!(current.retry, 1);
+
+        session.on_ack(0).unwrap();
+        let current = session.current().unwrap();
+        assert!(matches!(
+            current.session,
+            Some(ChunkSessionStatus::Received)
+        ));
+        assert_eq!(current.retry, 0);
+
+        session.next_to_send().unwrap();
+        assert_eq!(session.current().unwrap().number, Some(1));
+    }
+
+    #[test]
+    fn test_session_poll() {
+        let data = [0; 400];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data))
+            .with_max_retries(2)
+            .with_retry_policy(RetryPolicy::Exponential {
+                initial: Duration::from_millis(100),
+                max: Duration::from_secs(1),
+            });
+        assert_eq!(session.poll(1000u64).unwrap().unwrap().counter, 0);
+        assert!(session.poll(1099).unwrap().is_none());
+        assert_eq!(session.poll(1100).unwrap().unwrap().counter, 0);
+        assert!(session.poll(1299).unwrap().is_none());
+        assert_eq!(session.poll(1300).unwrap().unwrap().counter, 0);
+        assert!(matches!(
+            session.poll(2000),
+            Err(ChunkError::OverflowRetryCounter)
+        ));
+
+        session.on_ack(0).unwrap();
+        assert_eq!(session.poll(2000).unwrap().unwrap().counter, 1);
+        assert!(session.poll(2050).unwrap().is_none());
+        session.on_ack(1).unwrap();
+        assert!(session.poll(2100).unwrap().is_none());
+        assert!(session.is_done());
+    }
+
+    #[test]
+    fn test_session_restart() {
+        let data = [0; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data).with_session_id(7));
+        session.next_to_send().unwrap();
+        let mut stale = [0; 250];
+        let stale_len = session.chunk().encode_into(0, &mut stale).unwrap();
+        session.on_timeout().unwrap();
+        session.restart();
+        assert!(session.current().is_none());
+        let frame = session.next_to_send().unwrap();
+        assert_eq!(frame.counter, 0);
+        assert_eq!(frame.session_id, Some(8));
+
+        let mut out = [0; 250];
+        let len = session.chunk().encode_into(0, &mut out).unwrap();
+        let mut buf = [0; 1000];
+        let mut dechunker = DechunkerRef::new_in(&mut buf).with_session_ids();
+        assert_eq!(dechunker.push(&out[..len]).unwrap(), None);
+        assert!(matches!(
+            dechunker.push(&stale[..stale_len]),
+            Err(ChunkError::SessionMismatch {
+                expected: 8,
+                got: 7
+            })
+        ));
+
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data).with_session_id(u16::MAX));
+        session.restart();
+        assert_eq!(session.next_to_send().unwrap().session_id, Some(0));
+
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data));
+        session.next_to_send().unwrap();
+        session.restart();
+        assert_eq!(session.next_to_send().unwrap().session_id, None);
+    }
+
+    #[test]
+    fn test_session_abort() {
+        let data = [7; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data).with_session_id(3));
+        let mut buf = [0; 1000];
+        let mut dechunker = DechunkerRef::new_in(&mut buf).with_session_ids();
+        let mut out = [0; 250];
+        for _ in 0..2 {
+            let frame = session.next_to_send().unwrap();
+            let len = session
+                .chunk()
+                .encode_into(frame.counter, &mut out)
+                .unwrap();
+            dechunker.push(&out[..len]).unwrap();
+            session.on_ack(frame.counter).unwrap();
+        }
+        session.next_to_send().unwrap();
+
+        let frame = session.abort().unwrap();
+        assert_eq!(frame.session_id, Some(3));
+        assert_eq!(frame.counter, usize::MAX >> 1);
+        assert!(frame.payload.is_empty());
+        assert!(session.is_aborted());
+        assert!(matches!(
+            session.chunk().status.session,
+            Some(ChunkSessionStatus::Aborted)
+        ));
+        assert!(session.next_to_send().is_none());
+        assert!(session.poll(0).unwrap().is_none());
+        session.on_ack(2).unwrap();
+        assert!(session.is_aborted());
+        assert!(!session.is_done());
+        assert!(session.abort().is_none());
+
+        let len = session.chunk().encode_abort(&mut out).unwrap();
+        assert_eq!(len, 8 + 2);
+        assert!(matches!(
+            dechunker.push(&out[..len]),
+            Err(ChunkError::Aborted)
+        ));
+        assert!(dechunker.data().is_empty());
+        assert_eq!(dechunker.session_id(), None);
+
+        session.restart();
+        assert!(!session.is_aborted());
+        while let Some(frame) = session.next_to_send() {
+            let len = session
+                .chunk()
+                .encode_into(frame.counter, &mut out)
+                .unwrap();
+            dechunker.push(&out[..len]).unwrap();
+            session.on_ack(frame.counter).unwrap();
+        }
+        assert!(dechunker.is_complete());
+        assert_eq!(dechunker.session_id(), Some(4));
+        assert!(session.abort().is_none());
+
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data));
+        assert!(session.abort().is_none());
+        assert!(session.is_aborted());
+        assert!(session.next_to_send().is_none());
+    }
+
+    #[test]
+    fn test_session_poll_without_policy() {
+        let data = [0; 400];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data));
+        session.next_to_send().unwrap();
+        assert!(session.poll(0).unwrap().is_none());
+        assert!(session.poll(u64::MAX).unwrap().is_none());
+        session.on_ack(0).unwrap();
+        assert_eq!(session.poll(0).unwrap().unwrap().counter, 1);
+    }
+
+    #[test]
+    fn test_session_window() {
+        let data = [0; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data))
+            .with_window_size(3)
+            .with_max_retries(1);
+        for counter in 0..3 {
+            assert_eq!(session.next_to_send().unwrap().counter, counter);
+        }
+        assert!(session.next_to_send().is_none());
+        assert_eq!(session.in_flight(), 3);
+
+        // an ack past the oldest chunk doesn't slide the window
+        session.on_ack(1).unwrap();
+        assert!(session.next_to_send().is_none());
+        assert_eq!(session.on_timeout().unwrap().counter, 0);
+        assert!(matches!(
+            session.on_timeout(),
+            Err(ChunkError::OverflowRetryCounter)
+        ));
+        session.on_ack(0).unwrap();
+        session.on_ack(0).unwrap();
+        assert_eq!(session.next_to_send().unwrap().counter, 3);
+        assert_eq!(session.next_to_send().unwrap().counter, 4);
+        assert!(session.next_to_send().is_none());
+        assert!(matches!(
+            session.on_ack(5),
+            Err(ChunkError::UnexpectedChunkNumber { got: 5, .. })
+        ));
+
+        // retries within the window are counted per chunk
+        assert_eq!(session.on_timeout().unwrap().counter, 2);
+        session.on_ack(2).unwrap();
+        assert_eq!(session.on_timeout().unwrap().counter, 3);
+        let current = session.current().unwrap();
+        assert_eq!((current.number, current.retry), (Some(3), 1));
+        session.on_ack(4).unwrap();
+        session.on_ack(3).unwrap();
+        assert_eq!(session.in_flight(), 0);
+        assert!(session.is_done());
+    }
+
+    #[test]
+    fn test_session_window_poll() {
+        let data = [0; 1000];
+        let mut session = ChunkSession::new(Chunk::new(250, 0x10, &data))
+            .with_window_size(2)
+            .with_retry_policy(RetryPolicy::Fixed(Duration::from_millis(100)));
+        assert_eq!(session.poll(0u64).unwrap().unwrap().counter, 0);
+        assert_eq!(session.poll(10).unwrap().unwrap().counter, 1);
+        assert!(session.poll(50).unwrap().is_none());
+        assert_eq!(session.poll(100).unwrap().unwrap().counter, 0);
+        assert_eq!(session.poll(110).unwrap().unwrap().counter, 1);
+        session.on_ack(0).unwrap();
+        assert_eq!(session.poll(120).unwrap().unwrap().counter, 2);
+    }
+}
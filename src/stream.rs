@@ -0,0 +1,158 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{Checksum, Chunk, ChunkError, Endianness, MetaWidth};
+
+/**
+ * Frames data pulled lazily from a byte iterator, only the total length
+ * has to be known up front as it goes into the header.
+ * Frames are laid out by `Chunk`, the same as `Chunk::encode_into` gives for
+ * the whole data with the same options, and are built in a single internal
+ * buffer of max_chunk_size.
+ * For a `std::io::Read` source pass `reader.bytes().map_while(Result::ok)`,
+ * a failing reader then ends with `UnexpectedEof`.
+ */
+pub struct StreamChunker<I> {
+    source: I,
+    chunk: Chunk<'static>,
+    buf: Vec<u8>,
+}
+
+impl<I: Iterator<Item = u8>> StreamChunker<I> {
+    pub fn new(
+        max_chunk_size: usize,
+        topic: impl Into<u8>,
+        len: usize,
+        source: impl IntoIterator<IntoIter = I>,
+    ) -> Self {
+        StreamChunker {
+            source: source.into_iter(),
+            chunk: Chunk::gathered(max_chunk_size, topic.into(), len),
+            buf: vec![0; max_chunk_size],
+        }
+    }
+
+    /**
+     * See `Chunk::with_crc`
+     */
+    pub fn with_crc(mut self) -> Self {
+        self.chunk = self.chunk.with_crc();
+        self
+    }
+
+    /**
+     * See `Chunk::with_checksum`
+     */
+    pub fn with_checksum(mut self, checksum: &'static dyn Checksum) -> Self {
+        self.chunk = self.chunk.with_checksum(checksum);
+        self
+    }
+
+    /**
+     * See `Chunk::with_meta_width`
+     */
+    pub fn with_meta_width(mut self, meta_width: MetaWidth) -> Self {
+        self.chunk = self.chunk.with_meta_width(meta_width);
+        self
+    }
+
+    /**
+     * See `Chunk::with_endianness`
+     */
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.chunk = self.chunk.with_endianness(endianness);
+        self
+    }
+
+    pub fn counter(&self) -> usize {
+        self.chunk.counter()
+    }
+
+    /**
+     * Next wire frame, None once len bytes were framed.
+     * Fails as `Chunk::encode_into` does, e.g. with `ChunkSizeTooSmall` when
+     * a frame can't carry a payload byte, and with `UnexpectedEof` when the
+     * source ends before len bytes
+     */
+    pub fn next_frame(&mut self) -> Result<Option<&[u8]>, ChunkError> {
+        let counter = self.chunk.counter;
+        if counter > 0 && counter >= self.chunk.total_chunks() {
+            return Ok(None);
+        }
+        let source = &mut self.source;
+        let len = self
+            .chunk
+            .encode_frame_with(counter, true, &mut self.buf, |_, payload| {
+                for byte in payload {
+                    *byte = source.next().ok_or(ChunkError::UnexpectedEof)?;
+                }
+                Ok(())
+            })?;
+        self.chunk.counter += 1;
+        Ok(Some(&self.buf[..len]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Crc32, Dechunker};
+
+    #[test]
+    fn test_stream() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data).with_crc();
+        let mut stream = StreamChunker::new(250, 0x10, data.len(), data.iter().copied()).with_crc();
+        let mut dechunker = Dechunker::new().with_crc();
+        let mut result = None;
+        let mut out = [0; 250];
+        let mut counter = 0;
+        while let Some(frame) = stream.next_frame().unwrap() {
+            let len = chunk.encode_into(counter, &mut out).unwrap();
+            assert_eq!(frame, &out[..len]);
+            result = dechunker.push(frame).unwrap();
+            counter += 1;
+        }
+        assert_eq!(stream.counter(), 5);
+        assert_eq!(result.unwrap(), data);
+    }
+
+    #[test]
+    fn test_stream_short_source() {
+        let mut stream = StreamChunker::new(250, 0x10, 1000, [0; 10]);
+        assert!(matches!(
+            stream.next_frame(),
+            Err(ChunkError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_stream_options() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let chunk = Chunk::new(250, 0x10, &data)
+            .with_checksum(&Crc32)
+            .with_meta_width(MetaWidth::U16)
+            .with_endianness(Endianness::Big);
+        let mut stream = StreamChunker::new(250, 0x10, data.len(), data.iter().copied())
+            .with_checksum(&Crc32)
+            .with_meta_width(MetaWidth::U16)
+            .with_endianness(Endianness::Big);
+        for expected in chunk.to_frames() {
+            assert_eq!(stream.next_frame().unwrap().unwrap(), &expected[..]);
+        }
+        assert!(stream.next_frame().unwrap().is_none());
+
+        let mut stream = StreamChunker::new(10, 0x10, 5, [0; 5]);
+        assert!(matches!(
+            stream.next_frame(),
+            Err(ChunkError::ChunkSizeTooSmall { min: 18 })
+        ));
+    }
+
+    #[test]
+    fn test_stream_empty() {
+        let mut stream = StreamChunker::new(250, 0x10, 0, []);
+        assert_eq!(stream.next_frame().unwrap().unwrap().len(), 8 + 9);
+        assert!(stream.next_frame().unwrap().is_none());
+    }
+}
@@ -0,0 +1,65 @@
+use alloc::vec;
+
+use crate::{Chunk, ChunkError, Dechunker};
+
+/**
+ * Chunks data, feeds every wire frame through a `Dechunker` and checks the
+ * message comes back unchanged, for integration tests with fuzzed sizes.
+ * Fails like `ChunkBuilder::build` for unusable parameters, like
+ * `Dechunker::push` for a frame it rejects, with `FrameTooLarge` for a frame
+ * exceeding max_chunk_size, with `InvalidTopic` for a topic other than topic,
+ * with `LengthMismatch` when the message is incomplete or of the wrong length
+ * and with `MessageCrcMismatch` when its bytes differ
+ */
+pub fn verify_roundtrip(max_chunk_size: usize, topic: u8, data: &[u8]) -> Result<(), ChunkError> {
+    let chunk = Chunk::new(max_chunk_size, topic, data);
+    chunk.validate()?;
+    let mut dechunker = Dechunker::new();
+    let mut frame = vec![0; max_chunk_size];
+    let mut message = None;
+    for counter in 0..chunk.total_chunks() {
+        let len = chunk.encode_into(counter, &mut frame)?;
+        if len > max_chunk_size {
+            return Err(ChunkError::FrameTooLarge {
+                max: max_chunk_size,
+                got: len,
+            });
+        }
+        message = dechunker.push(&frame[..len])?.or(message);
+    }
+    dechunker.finish()?;
+    if let Some(got) = dechunker.topic().filter(|&got| got != topic) {
+        return Err(ChunkError::InvalidTopic(got));
+    }
+    let message = message.unwrap_or_default();
+    if message.len() != data.len() {
+        return Err(ChunkError::LengthMismatch {
+            expected: data.len(),
+            got: message.len(),
+        });
+    }
+    if message != data {
+        return Err(ChunkError::MessageCrcMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_verify_roundtrip() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        for max_chunk_size in [18, 19, 250, 1003, 1004, 1005, 2000] {
+            verify_roundtrip(max_chunk_size, 0x10, &data).unwrap();
+        }
+        verify_roundtrip(18, 0x10, &[]).unwrap();
+        verify_roundtrip(18, 0x10, &[1]).unwrap();
+        assert!(matches!(
+            verify_roundtrip(17, 0x10, &data),
+            Err(ChunkError::ChunkSizeTooSmall { .. })
+        ));
+    }
+}